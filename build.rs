@@ -0,0 +1,55 @@
+use std::{env, fs, path::Path, process::Command};
+
+// The server's own parser, so the list is checked the same way it's read
+#[path = "src/time/offset.rs"]
+mod offset;
+
+use offset::parse_offset;
+
+fn main() {
+    // Builds without a git checkout (like from a tarball) can set GIT_HASH
+    // themselves, or just be "unknown"
+    println!("cargo:rerun-if-env-changed=GIT_HASH");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+
+    let hash = std::env::var("GIT_HASH").ok().or_else(|| {
+        let output = Command::new("git").args(["rev-parse", "--short", "HEAD"]).output().ok()?;
+        output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_owned())
+    });
+
+    println!("cargo:rustc-env=GIT_HASH={}", hash.unwrap_or("unknown".into()));
+
+    time_zones();
+}
+
+/// Checks every offset in `time-zones.txt` and writes them out as a list the
+/// server includes, so a missing or broken list fails the build instead of
+/// the first request.
+fn time_zones() {
+    println!("cargo:rerun-if-changed=time-zones.txt");
+    println!("cargo:rerun-if-changed=src/time/offset.rs");
+
+    let path = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("time-zones.txt");
+    let zones = fs::read_to_string(&path)
+        .unwrap_or_else(|err| panic!("Couldn't read the time zone list at {}: {err}", path.display()));
+
+    let mut offsets = Vec::new();
+    for (number, line) in zones.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let offset = parse_offset(line)
+            .unwrap_or_else(|| panic!("time-zones.txt line {}: {line:?} isn't an offset like +05:30", number + 1));
+
+        if offsets.contains(&offset) {
+            panic!("time-zones.txt line {}: {line:?} is listed twice", number + 1);
+        }
+
+        offsets.push(offset);
+    }
+
+    if offsets.is_empty() {
+        panic!("time-zones.txt doesn't have any offsets");
+    }
+
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("time_zones.rs");
+    fs::write(out, format!("{offsets:?}")).unwrap();
+}
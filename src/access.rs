@@ -0,0 +1,35 @@
+use std::time::Instant;
+
+use axum::{extract::Request, middleware::Next, response::Response};
+use log::info;
+
+use crate::{connection::ClientIp, time::parse_query};
+
+/// Logs a single structured line for every request, regardless of the
+/// endpoint or whether a cat was actually made.
+///
+/// Each line contains the client's IP address, the method, path, response
+/// status, the client's time zone offset (if the query has one), and the total
+/// latency of the request.
+pub async fn log_request(ClientIp(ip): ClientIp, request: Request, next: Next) -> Response {
+    let start = Instant::now();
+
+    let method = request.method().clone();
+    let path = request.uri().path().to_owned();
+    let offset = request.uri().query()
+        .and_then(parse_query)
+        .map(|(_, offset)| offset.to_string())
+        .unwrap_or("-".into());
+
+    let response = next.run(request).await;
+
+    info!(
+        target: "access",
+        "ip={ip} method={method} path={path} status={} offset={offset} latency={:?}",
+        response.status().as_u16(),
+        start.elapsed(),
+    );
+
+    response
+}
+
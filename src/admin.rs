@@ -0,0 +1,134 @@
+use std::time::Instant;
+
+use axum::{extract::{Query, Request, State}, http::{header::{AUTHORIZATION, CONTENT_TYPE}, HeaderMap, StatusCode}, middleware::{self, Next}, response::{IntoResponse, Response}, routing::{get, post}, Json, Router};
+use chrono::Utc;
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::{draw::{purchase_cat_rgba, CatOptions, Format}, metrics::Snapshot, server::{self, AppState}};
+
+/// The admin endpoints, for operators who don't run Prometheus:
+/// - `GET /stats` returns the counters (and uptime) as JSON
+/// - `POST /reset` sets the counters back to zero
+/// - `GET /cat.rgba` draws a cat (with the same options as `/cat`) and returns
+///   its raw RGBA pixels, with the size in `X-Cat-Width` and `X-Cat-Height`
+///
+/// Every request needs an `Authorization: Bearer <token>` header with the
+/// token from `CAT_ADMIN_TOKEN`. These are meant to be nested under their own
+/// path (like `/admin`) so they can be firewalled off from the public routes.
+pub fn router(state: AppState) -> Router<AppState> {
+    let started = Instant::now();
+
+    Router::new()
+        .route("/stats", get(move |State(state): State<AppState>| async move {
+            Json(Stats { uptime: started.elapsed().as_secs(), metrics: state.metrics.snapshot() })
+        }))
+        .route("/reset", post(|State(state): State<AppState>| async move {
+            state.metrics.reset();
+            warn!("Metrics were reset");
+
+            StatusCode::NO_CONTENT
+        }))
+        .route("/cat.rgba", get(raw_cat))
+        .route_layer(middleware::from_fn_with_state(state, authorize))
+}
+
+/// What `/admin/stats` returns.
+#[derive(Serialize)]
+struct Stats {
+    /// How long the server has been up, in seconds.
+    uptime: u64,
+
+    #[serde(flatten)]
+    metrics: Snapshot,
+}
+
+/// Draws a cat without encoding it, for tools that want the pixels.
+async fn raw_cat(State(state): State<AppState>, Query(mut options): Query<CatOptions>) -> Response {
+    let seed = options.seed.unwrap_or_else(rand::random);
+    options.date.get_or_insert_with(|| Utc::now().date_naive());
+    let config = state.config;
+
+    let (width, height, pixels) = match server::render(&state, move || purchase_cat_rgba(&options, seed, config)).await {
+        Ok(Ok(cat)) => cat,
+        Ok(Err(err)) => return (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+        Err(response) => return response,
+    };
+
+    (
+        [(CONTENT_TYPE, Format::Rgba.content_type())],
+        [("x-cat-seed", seed.to_string()), ("x-cat-width", width.to_string()), ("x-cat-height", height.to_string())],
+        pixels,
+    ).into_response()
+}
+
+/// Turns away requests without the admin token with `401 Unauthorized`.
+async fn authorize(State(state): State<AppState>, headers: HeaderMap, request: Request, next: Next) -> Response {
+    let Some(token) = &state.config.admin_token else {
+        info!("Admin endpoint requested, but there's no admin token");
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let given = headers.get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    if !given.is_some_and(|given| same(given.trim().as_bytes(), token.as_bytes())) {
+        warn!("Admin endpoint requested with a bad token");
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    next.run(request).await
+}
+
+/// Compares two strings in time that only depends on their lengths, so
+/// secrets can't be guessed a byte at a time.
+fn same(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use axum::body::{to_bytes, Body};
+    use tower::ServiceExt;
+
+    use crate::{config::Config, metrics::Metrics};
+
+    use super::*;
+
+    /// Asks the admin endpoints for something, with the given token.
+    async fn get(state: &AppState, uri: &str, token: &str) -> Response {
+        let request = Request::get(uri).header(AUTHORIZATION, format!("Bearer {token}")).body(Body::empty()).unwrap();
+        router(state.clone()).with_state(state.clone()).oneshot(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn raw_cat_pixels() {
+        let config = Box::leak(Box::new(Config { admin_token: Some("token".into()), ..Config::default() }));
+        let state = AppState::new(config, Box::leak(Box::new(Metrics::new())));
+
+        let response = get(&state, "/cat.rgba?seed=5&scale=2", "token").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-cat-seed"], "5");
+        assert_eq!(response.headers()["x-cat-width"], "800");
+        assert_eq!(response.headers()["x-cat-height"], "512");
+
+        let pixels = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(pixels.len(), 800 * 512 * 4);
+
+        let again = to_bytes(get(&state, "/cat.rgba?seed=5&scale=2", "token").await.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(pixels, again);
+
+        assert_eq!(get(&state, "/cat.rgba?seed=5&scale=0", "token").await.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(get(&state, "/cat.rgba?seed=5", "nope").await.status(), StatusCode::UNAUTHORIZED);
+
+        // Raw cats wait their turn like any other, so a full queue turns them away
+        let config = Box::leak(Box::new(Config { queued_renders: 1, ..config.clone() }));
+        let metrics = Box::leak(Box::new(Metrics::new()));
+        metrics.queued_renders.store(1, Ordering::Relaxed);
+        let state = AppState::new(config, metrics);
+        assert_eq!(get(&state, "/cat.rgba?seed=5", "token").await.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
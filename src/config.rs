@@ -0,0 +1,559 @@
+use std::{env, fmt::Display, fs, net::SocketAddr, str::FromStr, sync::OnceLock, thread, time::Duration};
+
+use rand::Rng;
+use serde::{Deserialize, Deserializer};
+
+use crate::{draw::Rgb, logging::LogFormat, time::parse_offset};
+
+/// Settings that can be changed per instance, read from environment variables
+/// or a config file (see [Config::load]).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Where the server listens, set with `CAT_BIND`.
+    pub bind: SocketAddr,
+
+    /// The hour at which cats can be generated, set with `CAT_HOUR`. This
+    /// hour and the one twelve hours later (wrapping around midnight) are
+    /// both allowed hours for the client.
+    pub hour: u32,
+
+    /// The minute at which cats can be generated, set with `CAT_MINUTE`.
+    pub minute: u32,
+
+    /// The number of seconds of leeway for clients that think it's 2:22 a
+    /// little before it actually is somewhere, set with
+    /// `CAT_CLIENT_LEEWAY_BEFORE`. Early clients are more likely to be
+    /// cheating, so be careful widening this.
+    pub client_leeway_before: i64,
+
+    /// The number of seconds of leeway for clients that think it's still 2:22
+    /// a little after it stopped being 2:22 everywhere, like clients with
+    /// lagging clocks, set with `CAT_CLIENT_LEEWAY_AFTER`.
+    pub client_leeway_after: i64,
+
+    /// The maximum number of milliseconds the client's clock can be behind
+    /// the server's clock, set with `CAT_MAX_CLOCK_BEHIND_MS`.
+    #[serde(rename = "max_clock_behind_ms")]
+    pub max_clock_behind: i64,
+
+    /// The maximum number of milliseconds the client's clock can be ahead of
+    /// the server's clock, set with `CAT_MAX_CLOCK_AHEAD_MS`. This is
+    /// stricter than [Config::max_clock_behind] because a client from the
+    /// future is more likely to be cheating than a laggy one.
+    #[serde(rename = "max_clock_ahead_ms")]
+    pub max_clock_ahead: i64,
+
+    /// The templates for the "come back later" messages, which are shown on
+    /// the index page and drawn by [crate::draw::out_of_stock]. `{hour}` and
+    /// `{minute}` are replaced with [Config::hour] and [Config::minute].
+    ///
+    /// Set with `CAT_MESSAGES`, separated by `|`.
+    pub messages: Vec<String>,
+
+    /// Where the site is hosted, like `https://makea.cat`, for links that
+    /// have to be absolute (like in the sitemap). Set with `CAT_PUBLIC_URL`.
+    pub public_url: String,
+
+    /// The width of generated images, set with `CAT_WIDTH`.
+    pub width: i32,
+
+    /// The height of generated images, set with `CAT_HEIGHT`.
+    pub height: i32,
+
+    /// The most cats a single connection can request, set with
+    /// `CAT_MAX_CATS_PER_CONNECTION`.
+    #[serde(rename = "max_cats_per_connection")]
+    pub cats_per_connection: usize,
+
+    /// The most pixels a single request can render, counting every cat, set
+    /// with `CAT_MAX_PIXELS`.
+    pub max_pixels: u64,
+
+    /// The color of outlines, text, and details like eyes, set like `ff8800`
+    /// with `CAT_OUTLINE`. It's black (or white if [Config::dark]) if this is
+    /// missing.
+    pub outline: Option<Rgb>,
+
+    /// How far curves like the body and head can stray from a perfect
+    /// ellipse, set in pixels with `CAT_CURVE_TOLERANCE`. Smaller is smoother
+    /// but slower, which mostly matters for big cats.
+    pub curve_tolerance: f32,
+
+    /// The lowest value for each channel of random colors, set with
+    /// `CAT_COLOR_MIN`.
+    pub color_min: u8,
+
+    /// The highest value for each channel of random colors, set with
+    /// `CAT_COLOR_MAX`.
+    pub color_max: u8,
+
+    /// The most milliseconds a client's clock can lag by on top of the usual
+    /// limit because of a slow connection, set with
+    /// `CAT_MAX_RTT_ALLOWANCE_MS`. Clients that send their round-trip time as
+    /// `rtt` get half of it, up to this. Round-trip times are ignored if this
+    /// is 0, which it is by default.
+    #[serde(rename = "max_rtt_allowance_ms")]
+    pub max_rtt_allowance: i64,
+
+    /// How long a request can take before giving up with
+    /// `503 Service Unavailable`, set in milliseconds with `CAT_TIMEOUT_MS`.
+    #[serde(rename = "timeout_ms", deserialize_with = "millis")]
+    pub timeout: Duration,
+
+    /// The most requests that are handled at once, set with
+    /// `CAT_MAX_CONCURRENT_REQUESTS`. Any more wait their turn (and time out if
+    /// they wait too long).
+    #[serde(rename = "max_concurrent_requests")]
+    pub concurrent_requests: usize,
+
+    /// The biggest request body that's accepted, set in bytes with
+    /// `CAT_MAX_BODY_BYTES`. Cats are asked for with `GET`, so this can be
+    /// small.
+    #[serde(rename = "max_body_bytes")]
+    pub max_body: usize,
+
+    /// The most images that can be drawn at once, set with
+    /// `CAT_MAX_CONCURRENT_RENDERS`. This defaults to the number of CPUs.
+    #[serde(rename = "max_concurrent_renders")]
+    pub concurrent_renders: usize,
+
+    /// The most images that can be waiting to be drawn before giving up with
+    /// `503 Service Unavailable`, set with `CAT_MAX_QUEUED_RENDERS`.
+    #[serde(rename = "max_queued_renders")]
+    pub queued_renders: usize,
+
+    /// Whether the server starts in maintenance mode, where nobody gets cats,
+    /// set with `CAT_MAINTENANCE`.
+    pub maintenance: bool,
+
+    /// The odds of a cat being golden, as in one in this many, set with
+    /// `CAT_GOLDEN_ODDS`. Golden cats are turned off with 0.
+    pub golden_odds: u32,
+
+    /// Whether each client only gets one cat per window (per offset), set with
+    /// `CAT_ONE_PER_WINDOW`. Clients are told apart by their IP address.
+    pub one_per_window: bool,
+
+    /// Whether the index page always has the JavaScript for making a cat, even
+    /// when it isn't the right time anywhere, set with `CAT_ALWAYS_JS`. This is
+    /// for testing the page; cats are still only made at the right time.
+    pub always_js: bool,
+
+    /// Whether rejected requests get an image saying why (like `rejected:
+    /// wrong_time`) instead of the usual message, set with
+    /// `CAT_DEBUG_REASONS`. This is for figuring out why test requests fail,
+    /// and tells cheaters exactly what they got wrong.
+    pub debug_reasons: bool,
+
+    /// How many cats can be made in each window, across every client, set
+    /// with `CAT_STOCK_PER_WINDOW`. There's no limit without it.
+    pub stock_per_window: Option<u64>,
+
+    /// Whether the server is behind a proxy that sets `X-Real-IP` or
+    /// `X-Forwarded-For`, set with `CAT_TRUST_PROXY`.
+    pub trust_proxy: bool,
+
+    /// Whether logs are human-readable or JSON, set with `CAT_LOG_FORMAT` as
+    /// `pretty` (the default) or `json`.
+    pub log_format: LogFormat,
+
+    /// How likely each kind of tail is, set in a `[tails]` table in the config
+    /// file.
+    pub tails: TailWeights,
+
+    /// What random seeds for cats are picked with, set with `CAT_SEED`. This
+    /// makes a freshly started server hand out the same cats in the same
+    /// order, which is handy for demos. Seeds are truly random without it.
+    pub seed: Option<u64>,
+
+    /// The bearer token for the `/admin` endpoints, set with
+    /// `CAT_ADMIN_TOKEN`. Nobody can use them without it.
+    pub admin_token: Option<String>,
+
+    /// The key for signing the nonces the index page hands out, set with
+    /// `CAT_SECRET`. Cat requests don't need a nonce without it.
+    pub secret: Option<String>,
+
+    /// The only offsets that get cats, if set, written like `+01:00,+02:00`
+    /// in `CAT_ALLOWED_OFFSETS`. Offsets are stored the way clients send them.
+    #[serde(deserialize_with = "some_offsets")]
+    pub allowed_offsets: Option<Vec<i64>>,
+
+    /// Offsets that never get cats, written like `-05:00,-04:00` in
+    /// `CAT_BLOCKED_OFFSETS`.
+    #[serde(deserialize_with = "offsets")]
+    pub blocked_offsets: Vec<i64>,
+}
+
+/// How likely each kind of tail is. The defaults are the chances cats have
+/// always had.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TailWeights {
+    /// The chance of a straight tail, set with `CAT_TAIL_STRAIGHT`.
+    pub straight: Chance,
+
+    /// The chance of a straight tail being very long, set with
+    /// `CAT_TAIL_LONG`.
+    pub long: Chance,
+
+    /// The chance of a tail that isn't straight being curly instead of just
+    /// curved, set with `CAT_TAIL_CURLY`.
+    pub curly: Chance,
+}
+
+impl Default for TailWeights {
+    fn default() -> Self {
+        Self {
+            straight: Chance::new(1, 20),
+            long: Chance::new(1, 10),
+            curly: Chance::new(1, 2),
+        }
+    }
+}
+
+/// The chance of something happening, written like `1/20`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Chance {
+    numerator: u32,
+    denominator: u32,
+}
+
+impl Chance {
+    /// Makes a chance of `numerator` in `denominator`.
+    ///
+    /// # Panics
+    /// If the denominator is zero or smaller than the numerator.
+    pub const fn new(numerator: u32, denominator: u32) -> Self {
+        assert!(denominator > 0 && numerator <= denominator, "a chance must be between 0 and 1");
+        Self { numerator, denominator }
+    }
+
+    /// Randomly decides whether or not the thing happens.
+    pub fn roll(self, rng: &mut impl Rng) -> bool {
+        rng.gen_ratio(self.numerator, self.denominator)
+    }
+}
+
+impl FromStr for Chance {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (numerator, denominator) = s.split_once('/').ok_or("expected a chance like 1/20")?;
+
+        let numerator = numerator.trim().parse::<u32>().map_err(|err| err.to_string())?;
+        let denominator = denominator.trim().parse::<u32>().map_err(|err| err.to_string())?;
+
+        if denominator == 0 || numerator > denominator {
+            return Err(format!("{numerator}/{denominator} isn't between 0 and 1"));
+        }
+
+        Ok(Self { numerator, denominator })
+    }
+}
+
+impl TryFrom<String> for Chance {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            // Port 1474 is the port for my previous project plus one
+            bind: SocketAddr::from(([127, 0, 0, 1], 1474)),
+            hour: 2,
+            minute: 22,
+            client_leeway_before: 1,
+            client_leeway_after: 1,
+            max_clock_behind: 15_000,
+            max_clock_ahead: 5_000,
+            messages: vec![
+                "come back at {hour}:{minute}".into(),
+                "torna a {hour}:{minute}".into(),
+            ],
+            public_url: "https://makea.cat".into(),
+            width: 400,
+            height: 256,
+            cats_per_connection: 100,
+            max_pixels: 4_000_000,
+            outline: None,
+            curve_tolerance: 0.25,
+            // Light colors by default
+            color_min: 100,
+            color_max: 255,
+            max_rtt_allowance: 0,
+            timeout: Duration::from_millis(5_000),
+            concurrent_requests: 512,
+            max_body: 4 * 1024,
+            concurrent_renders: thread::available_parallelism().map_or(1, |cpus| cpus.get()),
+            queued_renders: 64,
+            maintenance: false,
+            golden_odds: 1_000,
+            one_per_window: false,
+            always_js: false,
+            debug_reasons: false,
+            stock_per_window: None,
+            trust_proxy: false,
+            log_format: LogFormat::default(),
+            tails: TailWeights::default(),
+            seed: None,
+            admin_token: None,
+            secret: None,
+            allowed_offsets: None,
+            blocked_offsets: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Reads the config from the config file and the environment, using
+    /// defaults for anything that's missing.
+    ///
+    /// The config file is TOML, and is read from wherever `CAT_CONFIG` points.
+    /// Keys are setting names without `CAT_`, like `width = 800` for
+    /// `CAT_WIDTH`, except that the tail chances go in a `[tails]` table (like
+    /// `straight = "1/20"`). Lists like `CAT_MESSAGES` are arrays, and colors,
+    /// offsets, and chances are strings. Environment variables win over the
+    /// file.
+    ///
+    /// # Panics
+    /// If there's a `CAT_CONFIG` that can't be read or isn't valid, or any
+    /// setting has a value that can't be used. A typo in the config shouldn't
+    /// quietly become the default.
+    pub fn load() -> Self {
+        let mut config = match env::var("CAT_CONFIG") {
+            Ok(path) => {
+                let file = fs::read_to_string(&path).unwrap_or_else(|err| panic!("Couldn't read config file {path}: {err}"));
+                toml::from_str(&file).unwrap_or_else(|err| panic!("Config file {path} isn't valid: {err}"))
+            }
+            Err(_) => Self::default(),
+        };
+
+        config.read_env();
+
+        if let Err(err) = config.check() {
+            panic!("Invalid config: {err}");
+        }
+
+        config
+    }
+
+    /// Overwrites every setting that has an environment variable.
+    fn read_env(&mut self) {
+        set("CAT_BIND", &mut self.bind, parse);
+        set("CAT_HOUR", &mut self.hour, parse);
+        set("CAT_MINUTE", &mut self.minute, parse);
+        set("CAT_CLIENT_LEEWAY_BEFORE", &mut self.client_leeway_before, parse);
+        set("CAT_CLIENT_LEEWAY_AFTER", &mut self.client_leeway_after, parse);
+        set("CAT_MAX_CLOCK_BEHIND_MS", &mut self.max_clock_behind, parse);
+        set("CAT_MAX_CLOCK_AHEAD_MS", &mut self.max_clock_ahead, parse);
+        set("CAT_MESSAGES", &mut self.messages, |messages| Ok(messages.split('|').map(str::to_owned).collect()));
+        set("CAT_PUBLIC_URL", &mut self.public_url, |url| Ok(url.to_owned()));
+        set("CAT_WIDTH", &mut self.width, parse);
+        set("CAT_HEIGHT", &mut self.height, parse);
+        set("CAT_MAX_CATS_PER_CONNECTION", &mut self.cats_per_connection, parse);
+        set("CAT_MAX_PIXELS", &mut self.max_pixels, parse);
+        set("CAT_OUTLINE", &mut self.outline, |outline| parse(outline).map(Some));
+        set("CAT_CURVE_TOLERANCE", &mut self.curve_tolerance, parse);
+        set("CAT_COLOR_MIN", &mut self.color_min, parse);
+        set("CAT_COLOR_MAX", &mut self.color_max, parse);
+        set("CAT_MAX_RTT_ALLOWANCE_MS", &mut self.max_rtt_allowance, parse);
+        set("CAT_TIMEOUT_MS", &mut self.timeout, |timeout| parse(timeout).map(Duration::from_millis));
+        set("CAT_MAX_CONCURRENT_REQUESTS", &mut self.concurrent_requests, parse);
+        set("CAT_MAX_BODY_BYTES", &mut self.max_body, parse);
+        set("CAT_MAX_CONCURRENT_RENDERS", &mut self.concurrent_renders, parse);
+        set("CAT_MAX_QUEUED_RENDERS", &mut self.queued_renders, parse);
+        set("CAT_MAINTENANCE", &mut self.maintenance, flag);
+        set("CAT_GOLDEN_ODDS", &mut self.golden_odds, parse);
+        set("CAT_ONE_PER_WINDOW", &mut self.one_per_window, flag);
+        set("CAT_ALWAYS_JS", &mut self.always_js, flag);
+        set("CAT_DEBUG_REASONS", &mut self.debug_reasons, flag);
+        set("CAT_STOCK_PER_WINDOW", &mut self.stock_per_window, |stock| parse(stock).map(Some));
+        set("CAT_TRUST_PROXY", &mut self.trust_proxy, flag);
+        set("CAT_LOG_FORMAT", &mut self.log_format, parse);
+        set("CAT_TAIL_STRAIGHT", &mut self.tails.straight, parse);
+        set("CAT_TAIL_LONG", &mut self.tails.long, parse);
+        set("CAT_TAIL_CURLY", &mut self.tails.curly, parse);
+        set("CAT_SEED", &mut self.seed, |seed| parse(seed).map(Some));
+        set("CAT_ADMIN_TOKEN", &mut self.admin_token, |token| Ok(Some(token.to_owned())));
+        set("CAT_SECRET", &mut self.secret, |secret| Ok(Some(secret.to_owned())));
+        set("CAT_ALLOWED_OFFSETS", &mut self.allowed_offsets, |offsets| offset_list(offsets.split(',')).map(Some));
+        set("CAT_BLOCKED_OFFSETS", &mut self.blocked_offsets, |offsets| offset_list(offsets.split(',')));
+    }
+
+    /// Makes sure every setting can be used, tidying up the ones that can be
+    /// written more than one way.
+    fn check(&mut self) -> Result<(), String> {
+        self.public_url = self.public_url.trim_end_matches('/').to_owned();
+        self.admin_token = self.admin_token.take().filter(|token| !token.is_empty());
+        self.secret = self.secret.take().filter(|secret| !secret.is_empty());
+
+        let problems = [
+            (self.hour >= 24, "hour must be below 24"),
+            (self.minute >= 60, "minute must be below 60"),
+            (self.client_leeway_before < 0 || self.client_leeway_after < 0, "client leeways can't be negative"),
+            (self.max_clock_behind < 0 || self.max_clock_ahead < 0, "clock limits can't be negative"),
+            (self.messages.is_empty() || self.messages.iter().any(String::is_empty), "messages can't be empty"),
+            (self.public_url.is_empty(), "public_url can't be empty"),
+            (self.width <= 0 || self.height <= 0, "width and height must be positive"),
+            (self.max_pixels < self.width.max(0) as u64 * self.height.max(0) as u64, "max_pixels must fit at least one default canvas"),
+            (self.curve_tolerance.is_nan() || self.curve_tolerance <= 0., "curve_tolerance must be positive"),
+            (self.color_min > self.color_max, "color_min can't be more than color_max"),
+            (self.max_rtt_allowance < 0, "max_rtt_allowance_ms can't be negative"),
+            (self.concurrent_requests == 0, "max_concurrent_requests must be positive"),
+            (self.concurrent_renders == 0, "max_concurrent_renders must be positive"),
+            (self.queued_renders == 0, "max_queued_renders must be positive"),
+        ];
+
+        match problems.into_iter().find(|&(problem, _)| problem) {
+            Some((_, problem)) => Err(problem.into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns whether or not random colors are mostly dark, meaning things
+    /// drawn on top of them should be light.
+    pub fn dark(&self) -> bool {
+        (self.color_min as u32 + self.color_max as u32) / 2 < 128
+    }
+
+    /// Returns every message with the time filled in.
+    pub fn messages(&self) -> impl Iterator<Item = String> + '_ {
+        self.messages.iter().map(|message| {
+            message
+                .replace("{hour}", &self.hour.to_string())
+                .replace("{minute}", &format!("{:0>2}", self.minute))
+        })
+    }
+}
+
+/// Overwrites a setting with its environment variable, if it's set.
+///
+/// # Panics
+/// If the variable is set to something that can't be parsed.
+fn set<T>(name: &str, setting: &mut T, parse: impl FnOnce(&str) -> Result<T, String>) {
+    let Ok(value) = env::var(name) else {
+        return;
+    };
+
+    match parse(&value) {
+        Ok(value) => *setting = value,
+        Err(err) => panic!("{name} is {value:?}, which can't be used: {err}"),
+    }
+}
+
+/// Parses an environment variable the usual way.
+fn parse<T: FromStr<Err: Display>>(value: &str) -> Result<T, String> {
+    value.trim().parse().map_err(|err: T::Err| err.to_string())
+}
+
+/// Parses an environment variable that turns something on or off, like `1` or
+/// `false`.
+fn flag(value: &str) -> Result<bool, String> {
+    match value.trim().to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" | "" => Ok(false),
+        _ => Err("expected true or false".into()),
+    }
+}
+
+/// Parses a list of offsets like `+01:00` and `-05:30`.
+fn offset_list<'a>(offsets: impl IntoIterator<Item = &'a str>) -> Result<Vec<i64>, String> {
+    offsets.into_iter()
+        .map(|offset| parse_offset(offset).ok_or_else(|| format!("{} isn't an offset like +05:30", offset.trim())))
+        .collect()
+}
+
+/// Deserializes a list of offsets, like `["+01:00", "-05:30"]`.
+fn offsets<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<i64>, D::Error> {
+    let offsets = Vec::<String>::deserialize(deserializer)?;
+    offset_list(offsets.iter().map(String::as_str)).map_err(serde::de::Error::custom)
+}
+
+/// Deserializes a list of offsets that's only there if it's set.
+fn some_offsets<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<i64>>, D::Error> {
+    offsets(deserializer).map(Some)
+}
+
+/// Deserializes a duration written in milliseconds.
+fn millis<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    u64::deserialize(deserializer).map(Duration::from_millis)
+}
+
+/// Returns the config for this instance, reading it the first time it's used.
+pub fn config() -> &'static Config {
+    static CONFIG: OnceLock<Config> = OnceLock::new();
+    CONFIG.get_or_init(Config::load)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_toml() {
+        let config: Config = toml::from_str(r##"
+            width = 800
+            hour = 14
+            outline = "#ff8800" # orange
+            timeout_ms = 2000
+            messages = ["back at {hour}:{minute}"]
+            allowed_offsets = ["+05:30", "-06:00"]
+
+            [tails]
+            straight = "1/2"
+        "##).unwrap();
+
+        assert_eq!(config.width, 800);
+        assert_eq!(config.height, Config::default().height);
+        assert_eq!(config.outline, Some(Rgb { r: 0xff, g: 0x88, b: 0x00 }));
+        assert_eq!(config.timeout, Duration::from_secs(2));
+        assert_eq!(config.messages().collect::<Vec<_>>(), ["back at 14:22"]);
+        assert_eq!(config.allowed_offsets, Some(vec![-330, 360]));
+        assert_eq!(config.tails.straight, Chance::new(1, 2));
+        assert_eq!(config.tails.long, TailWeights::default().long);
+    }
+
+    #[test]
+    fn bad_toml_is_an_error() {
+        // An unquoted color is a comment, which isn't a value
+        assert!(toml::from_str::<Config>("outline = #ff8800").is_err());
+        assert!(toml::from_str::<Config>("width = \"wide\"").is_err());
+        assert!(toml::from_str::<Config>("colour_min = 10").is_err());
+        assert!(toml::from_str::<Config>("blocked_offsets = [\"+01:00\", \"+1\"]").is_err());
+        assert!(toml::from_str::<Config>("[tails]\nstraight = \"2/1\"").is_err());
+    }
+
+    #[test]
+    fn checks_values() {
+        assert!(Config::default().check().is_ok());
+        assert!(Config { hour: 24, ..Config::default() }.check().is_err());
+        assert!(Config { width: 0, ..Config::default() }.check().is_err());
+        assert!(Config { color_min: 200, color_max: 100, ..Config::default() }.check().is_err());
+        assert!(Config { queued_renders: 0, ..Config::default() }.check().is_err());
+        assert!(Config { max_pixels: 0, ..Config::default() }.check().is_err());
+        assert!(Config { max_pixels: 400 * 256, ..Config::default() }.check().is_ok());
+
+        let mut config = Config { public_url: "https://example.com/".into(), secret: Some(String::new()), ..Config::default() };
+        config.check().unwrap();
+        assert_eq!(config.public_url, "https://example.com");
+        assert_eq!(config.secret, None);
+    }
+
+    #[test]
+    fn parses_env_values() {
+        assert_eq!(parse::<u32>(" 12 "), Ok(12));
+        assert!(parse::<u32>("twelve").is_err());
+        assert_eq!(flag("on"), Ok(true));
+        assert_eq!(flag("0"), Ok(false));
+        assert!(flag("maybe").is_err());
+        assert_eq!(offset_list("+01:00,-05:30".split(',')), Ok(vec![-60, 330]));
+        assert!(offset_list("+01:00,+1".split(',')).is_err());
+    }
+}
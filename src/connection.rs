@@ -0,0 +1,112 @@
+use std::{net::{IpAddr, SocketAddr}, sync::{atomic::{AtomicUsize, Ordering}, Arc}};
+
+use axum::{async_trait, extract::{connect_info::Connected, ConnectInfo, FromRef, FromRequestParts, Request, State}, http::{request::Parts, HeaderMap, StatusCode}, middleware::Next, response::{IntoResponse, Response}, serve::IncomingStream};
+use log::warn;
+use tokio::sync::Semaphore;
+
+use crate::config::Config;
+
+/// Information about a single connection.
+#[derive(Clone)]
+pub struct Connection {
+    /// The address on the other end of the socket. This is the proxy, not the
+    /// client, when running behind one (see [ClientIp]).
+    pub peer: SocketAddr,
+
+    /// The number of cats requested on this connection. A new counter is made
+    /// for every connection, so keep-alive and HTTP/2 clients can't request an
+    /// unlimited number of cats without reconnecting.
+    cats: Arc<AtomicUsize>,
+}
+
+impl Connection {
+    /// Counts another cat requested on this connection, returning whether it's
+    /// still within the configured limit. Requests that send more than one cat
+    /// (like `/cat/stream`) count each one past the first themselves.
+    pub fn count_cat(&self, config: &Config) -> bool {
+        let count = self.cats.fetch_add(1, Ordering::Relaxed) + 1;
+
+        if count > config.cats_per_connection {
+            warn!("Connection requested {count} cats, over the limit of {}", config.cats_per_connection);
+            return false;
+        }
+
+        true
+    }
+}
+
+impl Connected<IncomingStream<'_>> for Connection {
+    fn connect_info(stream: IncomingStream<'_>) -> Self {
+        Self {
+            peer: stream.remote_addr(),
+            cats: Arc::default(),
+        }
+    }
+}
+
+/// Counts the cats requested on each connection, responding with
+/// `429 Too Many Requests` once there have been more than the configured
+/// limit.
+pub async fn limit_cats(State(config): State<&'static Config>, ConnectInfo(connection): ConnectInfo<Connection>, request: Request, next: Next) -> Response {
+    if !connection.count_cat(config) {
+        return StatusCode::TOO_MANY_REQUESTS.into_response();
+    }
+
+    next.run(request).await
+}
+
+/// Only lets as many requests be handled at once as `requests` has permits
+/// (see [Config::concurrent_requests]). The rest wait for their turn, which
+/// counts towards their timeout.
+pub async fn limit_requests(State(requests): State<Arc<Semaphore>>, request: Request, next: Next) -> Response {
+    let _permit = requests.acquire().await.expect("the request semaphore is never closed");
+
+    next.run(request).await
+}
+
+/// The IP address of the client making a request.
+///
+/// Behind a proxy, every connection comes from the proxy, so the real address
+/// is read from `X-Real-IP` or `X-Forwarded-For` instead. Anyone can send
+/// those headers, so they're only trusted if `CAT_TRUST_PROXY` is set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ClientIp
+where
+    &'static Config: FromRef<S>,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let peer = parts.extensions.get::<ConnectInfo<Connection>>()
+            .map(|ConnectInfo(connection)| connection.peer.ip())
+            .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok(ClientIp(client_ip(<&Config>::from_ref(state), &parts.headers, peer)))
+    }
+}
+
+/// Figures out the client's IP address from the request headers, falling back
+/// to the address of the socket's peer.
+///
+/// The last address in `X-Forwarded-For` is used, since that's the one added
+/// by the proxy itself, and any before it could have come from the client.
+pub fn client_ip(config: &Config, headers: &HeaderMap, peer: IpAddr) -> IpAddr {
+    if !config.trust_proxy {
+        return peer;
+    }
+
+    let real_ip = headers.get("x-real-ip")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok());
+
+    let forwarded_for = || headers.get_all("x-forwarded-for").iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .last()
+        .and_then(|value| value.trim().parse().ok());
+
+    real_ip.or_else(forwarded_for).unwrap_or(peer)
+}
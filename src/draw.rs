@@ -1,121 +1,1649 @@
 use core::f32;
-use std::{io::BufWriter, sync::OnceLock};
+use std::{borrow::Cow, cell::Cell, io::BufWriter, str::FromStr, sync::OnceLock, time::{Duration, Instant}};
 
 use anyhow::Result;
-use font_kit::{handle::Handle, source::SystemSource};
-use lyon_geom::{euclid::Transform2D, Angle, Arc, Point};
-use rand::Rng;
+use chrono::NaiveDate;
+use font_kit::{font::Font, handle::Handle, source::SystemSource};
+use image::{codecs::jpeg::JpegEncoder, ExtendedColorType};
+use log::error;
+use lyon_geom::{euclid::{default::Box2D, Transform2D}, Angle, Arc, Point};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 use raqote::*;
+use serde::{de::{Error as _, Unexpected}, Deserialize, Deserializer, Serialize};
+
+use crate::{config::Config, svg::Svg};
+
+/// Picks which of the default messages [out_of_stock] draws.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Variant {
+    /// The first message, "come back at 2:22".
+    ComeBack,
+    /// The second message, "torna a 2:22".
+    Torna,
+}
+
+/// Draws one of the configured "come back at 2:22" messages, returning a PNG.
+/// The message is random unless a `variant` is provided, and there's a frame
+/// around it if `border` (in pixels) isn't zero.
+///
+/// Like with cats, the same seed always picks the same message in the same
+/// place.
+pub fn out_of_stock(config: &Config, variant: Option<Variant>, seed: u64, border: u32) -> Vec<u8> {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+    let messages = config.messages().collect::<Vec<_>>();
+    let index = rng.gen_range(0..messages.len());
+    let index = variant.map(|variant| variant as usize)
+        .filter(|&index| index < messages.len())
+        .unwrap_or(index);
+
+    draw_message(config, &messages[index], &mut rng, border)
+}
+
+/// Draws some text somewhere random (according to the seed) on an otherwise
+/// empty canvas, returning a PNG. There's a frame around it if `border` (in
+/// pixels) isn't zero.
+pub fn message(config: &Config, text: &str, seed: u64, border: u32) -> Vec<u8> {
+    draw_message(config, text, &mut ChaCha8Rng::seed_from_u64(seed), border)
+}
+
+/// Draws some text somewhere on an otherwise empty canvas, returning a PNG.
+fn draw_message(config: &Config, text: &str, rng: &mut impl Rng, border: u32) -> Vec<u8> {
+    let (width, height) = (config.width, config.height);
+
+    let font = font();
+
+    // Pick somewhere the text fits
+    let text_width = text_width(&font, 24., text);
+    let (x, y) = (
+        rng.gen_range(8.0..(width as f32 - 8. - text_width).max(8.1)),
+        rng.gen_range(25.0..(height as f32 - 8.).max(25.1)),
+    );
+
+    with_canvas(config, width, height, |dt| {
+        // The text can't be rotated because of a bug with raqote.
+        // Hopefully this will change!
+
+        let colors = ColorScheme::configured(config);
+        dt.draw_text(&font, 24., text, Point::new(x, y), &colors.outline(), &DRAW);
+        draw_border(dt, border, &colors, &DRAW);
+
+        canvas_to_png(dt, None).unwrap_or_else(|err| {
+            error!("Couldn't encode message: {err}");
+            error_image()
+        })
+    })
+}
+
+/// Draws a sad cat hanging its head, with "nice try" under it, for clients
+/// that look like they're cheating, returning a PNG. There's a frame around it
+/// if `border` (in pixels) isn't zero.
+pub fn nice_try(config: &Config, seed: u64, border: u32) -> Vec<u8> {
+    const TEXT: &str = "nice try / bon intent";
+
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let (width, height) = (config.width, config.height);
+
+    // Sad cats' ears droop, and they're never golden
+    let features = CatFeatures {
+        ears: Ears::Folded,
+        golden: false,
+        ..CatFeatures::roll(&mut rng, &CatOptions::default(), config)
+    };
+
+    let font = font();
+
+    with_canvas(config, width, height, |dt| {
+        let colors = ColorScheme::configured(config);
+        let (width, height) = (width as f32, height as f32);
+        let size = width.min(height) / 256.;
+
+        // The head is big and in the middle, tilted down to one side
+        let tilt = if rng.gen::<bool>() { 1. } else { -1. } * rng.gen_range(10.0..20.0);
+        dt.set_transform(&Transform::scale(1.8 * size, 1.8 * size)
+            .then_rotate(Angle::degrees(tilt))
+            .then_translate(Vector::new(width / 2., height / 2. - 16. * size)));
+
+        draw_head(dt, &mut rng, &features, &colors, &DRAW, config.curve_tolerance);
+
+        // A frown under the nose, and a tear under one eye
+        let mut pb = PathBuilder::new();
+        pb.move_to(-6., 17.);
+        pb.quad_to(0., 12., 6., 17.);
+        dt.stroke(&pb.finish(), &colors.outline(), &StrokeStyle {
+            cap: LineCap::Round,
+            width: 1.5,
+            ..StrokeStyle::default()
+        }, &DRAW);
+
+        let mut pb = PathBuilder::new();
+        pb.move_to(-9., -1.);
+        pb.quad_to(-5., 6., -9., 7.);
+        pb.quad_to(-13., 6., -9., -1.);
+        pb.close();
+        dt.fill(&pb.finish(), &Source::Solid(SolidSource { r: 0x6c, g: 0xb4, b: 0xff, a: 0xff }), &DRAW);
+
+        dt.set_transform(&Transform::identity());
+
+        let x = (width - text_width(&font, 24., TEXT)) / 2.;
+        dt.draw_text(&font, 24., TEXT, Point::new(x.max(8.), height - 16. * size), &colors.outline(), &DRAW);
+        draw_border(dt, border, &colors, &DRAW);
+
+        canvas_to_png(dt, None).unwrap_or_else(|err| {
+            error!("Couldn't encode sad cat: {err}");
+            error_image()
+        })
+    })
+}
+
+/// Returns the font that all text is drawn with.
+///
+/// The font is only looked up once, but it has to be loaded once per thread,
+/// since loaded fonts can't be shared between threads. Those copies are kept
+/// around too, so this is cheap after the first few images.
+fn font() -> Font {
+    static HANDLE: OnceLock<Handle> = OnceLock::new();
+
+    thread_local! {
+        static FONT: Font = HANDLE.get_or_init(|| {
+            SystemSource::new()
+            .select_by_postscript_name("DejaVuSans").unwrap()
+        }).load().unwrap();
+    }
+
+    FONT.with(Font::clone)
+}
+
+/// Returns the width of some text in the given font.
+fn text_width(font: &Font, size: f32, text: &str) -> f32 {
+    let units_per_em = font.metrics().units_per_em as f32;
+
+    text.chars()
+        .filter_map(|c| font.glyph_for_char(c))
+        .filter_map(|glyph| font.advance(glyph).ok())
+        .map(|advance| advance.x() * size / units_per_em)
+        .sum()
+}
+
+/// Options for how a cat is drawn.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CatOptions {
+    /// How old the cat is.
+    pub age: Age,
+
+    /// The multiplier for the size of the image.
+    pub scale: u32,
+
+    /// How many cats to draw.
+    pub count: u32,
+
+    /// The seed for the cat's randomness. Cats drawn with the same seed and
+    /// options are identical. It's random if this is missing.
+    pub seed: Option<u64>,
+
+    /// The backdrop drawn behind the cats. There's none (it's transparent) if
+    /// this is missing.
+    pub scene: Option<Scene>,
+
+    /// The color of the cats' eyes. It's random if this is missing.
+    pub eyes: Option<EyeColor>,
+
+    /// How the cats are holding themselves. It's random if this is missing.
+    pub pose: Option<Pose>,
+
+    /// The shape of the cats' ears. They're usually upright if this is
+    /// missing.
+    pub ears: Option<Ears>,
+
+    /// Something extra drawn with the cats. There's nothing extra if this is
+    /// missing.
+    pub style: Option<Style>,
+
+    /// How many legs the cats have. They usually have four if this is
+    /// missing.
+    pub legs: Option<Legs>,
+
+    /// How the cats are feeling, which picks their pose, ears, eyes, and how
+    /// they hold their tail and head all at once. Anything set on its own (like
+    /// `pose`) still wins. They're not feeling anything in particular if this
+    /// is missing.
+    pub mood: Option<Mood>,
+
+    /// How the edges of shapes are smoothed.
+    pub aa: Antialias,
+
+    /// The color of the cats' outlines and details, like `ffffff`. It's the
+    /// configured color if this is missing.
+    pub outline: Option<Rgb>,
+
+    /// How thick a frame is drawn around the edge of the image, in pixels, like
+    /// the one around cats on the index. There's no frame if this is 0.
+    pub border: u32,
+
+    /// Whether the cats have a drop shadow, set with `shadow=1`.
+    #[serde(deserialize_with = "switch")]
+    pub shadow: bool,
+
+    /// A theme that every cat made with it shares. There's none if this is
+    /// missing.
+    pub themed: Option<Theme>,
+
+    /// The day the cats are made for (in UTC), which picks the color of
+    /// [Theme::Daily]. It's filled in by whatever asked for the cat, since
+    /// drawing a cat never looks at the clock, and there's no tint without it.
+    #[serde(skip)]
+    pub date: Option<NaiveDate>,
+
+    /// Whether plain black eyes get a little shine too, set with `shine=1`.
+    /// Colored eyes always have one.
+    #[serde(deserialize_with = "switch")]
+    pub shine: bool,
+
+    /// Whether there's a little paw print in the bottom right corner, so cats
+    /// can be traced back to here. It's on unless it's turned off with
+    /// `mark=0`.
+    #[serde(deserialize_with = "switch")]
+    pub mark: bool,
+
+    /// Whether cats are kept inside the canvas however they're rotated, set
+    /// with `fit=1`. Cats turned on their side are shrunk a little to fit, and
+    /// otherwise poke out of the top or bottom. Very long tails still don't
+    /// fit.
+    #[serde(deserialize_with = "switch")]
+    pub fit: bool,
+
+    /// The file format of the image.
+    pub format: Format,
+
+    /// The quality of lossy formats like JPEG, from 1 to 100.
+    #[serde(rename = "q")]
+    pub quality: u8,
+}
+
+/// Reads an option that's on or off, like `1` or `true`. JSON can use a real
+/// `true` or `false` too.
+pub(crate) fn switch<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Switch {
+        Bool(bool),
+        Text(String),
+    }
+
+    let text = match Switch::deserialize(deserializer)? {
+        Switch::Bool(on) => return Ok(on),
+        Switch::Text(text) => text,
+    };
+
+    match text.as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        other => Err(D::Error::invalid_value(Unexpected::Str(other), &"1 or 0")),
+    }
+}
+
+impl Default for CatOptions {
+    fn default() -> Self {
+        Self {
+            age: Age::default(),
+            scale: 1,
+            count: 1,
+            seed: None,
+            scene: None,
+            eyes: None,
+            pose: None,
+            ears: None,
+            style: None,
+            legs: None,
+            mood: None,
+            aa: Antialias::default(),
+            outline: None,
+            border: 0,
+            shadow: false,
+            fit: false,
+            shine: false,
+            mark: true,
+            themed: None,
+            date: None,
+            format: Format::Png,
+            quality: 90,
+        }
+    }
+}
+
+/// Builds up the options for a cat and draws it, for using this as a library.
+/// Cats can be drawn as an image in any [Format] with [CatBuilder::render], or
+/// as an SVG document with [CatBuilder::render_svg].
+///
+/// Anything that isn't set is the same as leaving it out of a request. Cats are
+/// drawn with the [default config](Config::default) (which never reads the
+/// environment) unless another one is given with [CatBuilder::config].
+#[derive(Debug, Clone, Default)]
+pub struct CatBuilder {
+    options: CatOptions,
+    config: Cow<'static, Config>,
+}
+
+impl CatBuilder {
+    /// Starts with the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the seed, so the same cat is drawn every time.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.options.seed = Some(seed);
+        self
+    }
+
+    /// Sets how old the cats are.
+    pub fn age(mut self, age: Age) -> Self {
+        self.options.age = age;
+        self
+    }
+
+    /// Sets the multiplier for the size of the image.
+    pub fn scale(mut self, scale: u32) -> Self {
+        self.options.scale = scale;
+        self
+    }
+
+    /// Sets how many cats are drawn.
+    pub fn count(mut self, count: u32) -> Self {
+        self.options.count = count;
+        self
+    }
+
+    /// Sets the backdrop drawn behind the cats.
+    pub fn scene(mut self, scene: Scene) -> Self {
+        self.options.scene = Some(scene);
+        self
+    }
+
+    /// Sets the color of the cats' eyes.
+    pub fn eyes(mut self, eyes: EyeColor) -> Self {
+        self.options.eyes = Some(eyes);
+        self
+    }
+
+    /// Sets how the cats are holding themselves.
+    pub fn pose(mut self, pose: Pose) -> Self {
+        self.options.pose = Some(pose);
+        self
+    }
+
+    /// Sets the shape of the cats' ears.
+    pub fn ears(mut self, ears: Ears) -> Self {
+        self.options.ears = Some(ears);
+        self
+    }
+
+    /// Sets something extra drawn with the cats.
+    pub fn style(mut self, style: Style) -> Self {
+        self.options.style = Some(style);
+        self
+    }
+
+    /// Sets how many legs the cats have.
+    pub fn legs(mut self, legs: Legs) -> Self {
+        self.options.legs = Some(legs);
+        self
+    }
+
+    /// Sets how the cats are feeling.
+    pub fn mood(mut self, mood: Mood) -> Self {
+        self.options.mood = Some(mood);
+        self
+    }
+
+    /// Sets how the edges of shapes are smoothed.
+    pub fn antialias(mut self, aa: Antialias) -> Self {
+        self.options.aa = aa;
+        self
+    }
+
+    /// Sets the color of the cats' outlines and details.
+    pub fn outline(mut self, outline: Rgb) -> Self {
+        self.options.outline = Some(outline);
+        self
+    }
+
+    /// Sets how thick a frame is drawn around the image, in pixels.
+    pub fn border(mut self, border: u32) -> Self {
+        self.options.border = border;
+        self
+    }
+
+    /// Sets whether the cats have a drop shadow.
+    pub fn shadow(mut self, shadow: bool) -> Self {
+        self.options.shadow = shadow;
+        self
+    }
+
+    /// Sets whether cats are kept inside the canvas however they're rotated.
+    pub fn fit(mut self, fit: bool) -> Self {
+        self.options.fit = fit;
+        self
+    }
+
+    /// Sets whether plain black eyes get a shine too.
+    pub fn shine(mut self, shine: bool) -> Self {
+        self.options.shine = shine;
+        self
+    }
+
+    /// Sets whether there's a little paw print in the corner.
+    pub fn mark(mut self, mark: bool) -> Self {
+        self.options.mark = mark;
+        self
+    }
+
+    /// Sets a theme that every cat made with it shares.
+    pub fn themed(mut self, theme: Theme) -> Self {
+        self.options.themed = Some(theme);
+        self
+    }
+
+    /// Sets the day the cat is made for, which picks the color of
+    /// [Theme::Daily].
+    pub fn date(mut self, date: NaiveDate) -> Self {
+        self.options.date = Some(date);
+        self
+    }
+
+    /// Sets the file format of the image, and the quality for lossy ones.
+    pub fn format(mut self, format: Format, quality: u8) -> Self {
+        self.options.format = format;
+        self.options.quality = quality;
+        self
+    }
+
+    /// Sets the config the cats are drawn with, like the size of the canvas and
+    /// the range of colors.
+    pub fn config(mut self, config: &'static Config) -> Self {
+        self.config = Cow::Borrowed(config);
+        self
+    }
+
+    /// Sets the width of the canvas (before it's scaled), in pixels. This
+    /// changes the config the cats are drawn with.
+    pub fn width(mut self, width: i32) -> Self {
+        self.config.to_mut().width = width;
+        self
+    }
+
+    /// Sets the height of the canvas (before it's scaled), in pixels. This
+    /// changes the config the cats are drawn with.
+    pub fn height(mut self, height: i32) -> Self {
+        self.config.to_mut().height = height;
+        self
+    }
+
+    /// Returns the options built up so far.
+    pub fn options(&self) -> &CatOptions {
+        &self.options
+    }
+
+    /// Draws the cats, with a random seed if one wasn't set.
+    pub fn render(&self) -> Result<Cat, CanvasError> {
+        purchase_cat(&self.options, self.options.seed.unwrap_or_else(rand::random), &self.config)
+    }
+
+    /// Like [CatBuilder::render], but draws the cats as an SVG document (see
+    /// [purchase_cat_svg]), ignoring the format.
+    pub fn render_svg(&self) -> Result<String, CanvasError> {
+        purchase_cat_svg(&self.options, self.options.seed.unwrap_or_else(rand::random), &self.config)
+    }
+}
+
+impl From<CatOptions> for CatBuilder {
+    fn from(options: CatOptions) -> Self {
+        Self { options, ..Self::default() }
+    }
+}
+
+/// Everything about what a cat looks like, without the exact shapes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CatFeatures {
+    /// Which way the cat is looking.
+    pub facing: Facing,
+    /// How old the cat is.
+    pub age: Age,
+    /// How the cat is holding itself.
+    pub pose: Pose,
+    /// The shape of the cat's ears.
+    pub ears: Ears,
+    /// The color of the cat's eyes.
+    pub eyes: EyeColor,
+    /// How open the cat's eyes are.
+    pub lids: Lids,
+    /// Whether the cat's eyes have a little white highlight.
+    pub shine: bool,
+    /// Whether the cat's tail points up instead of out behind it.
+    pub tail_up: bool,
+    /// Whether the cat's head is tilted to one side.
+    pub tilted: bool,
+    /// The shape of the cat's tail.
+    pub tail: Tail,
+    /// How many whiskers are on each side of the cat's face.
+    pub whiskers: u32,
+    /// Whether the cat's paws have little toe lines.
+    pub toes: bool,
+    /// Whether the cat is a very rare golden cat, with gold colors and some
+    /// sparkles.
+    pub golden: bool,
+    /// How many legs the cat has.
+    pub legs: Legs,
+    /// Whether the cat has a lighter belly.
+    pub belly: bool,
+}
+
+impl CatFeatures {
+    /// Picks the features for a cat, using the ones in `options` where they're
+    /// set, and the odds in `config` otherwise.
+    ///
+    /// Everything is always rolled (even if it's set in `options`) so that
+    /// picking one feature doesn't change the rest of the cat.
+    pub fn roll(rng: &mut impl Rng, options: &CatOptions, config: &Config) -> Self {
+        // 50% chance for the cat to face the other way
+        let facing = if rng.gen::<bool>() { Facing::Left } else { Facing::Right };
+
+        let pose = if rng.gen::<bool>() { Pose::Standing } else { Pose::Sitting };
+
+        // 10% chance for folded ears
+        let ears = if rng.gen_ratio(1, 10) { Ears::Folded } else { Ears::Upright };
+
+        let eyes = EyeColor::ALL[rng.gen_range(0..EyeColor::ALL.len())];
+
+        // 5% chance for a straight line tail, with an additional 10% chance for
+        // it to be very long. Otherwise, it's 50/50 between cubic and quadratic.
+        // (Unless the config says otherwise.)
+        let tails = config.tails;
+        let tail = if tails.straight.roll(rng) {
+            if tails.long.roll(rng) { Tail::Long } else { Tail::Straight }
+        } else if tails.curly.roll(rng) {
+            Tail::Curly
+        } else {
+            Tail::Curved
+        };
+
+        // Two or three whiskers on each side
+        let whiskers = rng.gen_range(2..=3);
+
+        // 2/3 chance for little toe lines on the paws
+        let toes = rng.gen_ratio(2, 3);
+
+        // A very rare golden cat, which is always a golden cat for the same seed
+        let odds = config.golden_odds;
+        let golden = rng.gen_ratio(1, odds.max(1)) && odds > 0;
+
+        // 2% chance for a three-legged cat
+        let legs = if rng.gen_ratio(1, 50) { Legs::Three } else { Legs::Four };
+
+        // 1/3 chance for a lighter belly
+        let belly = rng.gen_ratio(1, 3);
+
+        // Moods only change how the cat holds itself, so they don't need to
+        // roll anything
+        let mood = options.mood.map(Mood::features).unwrap_or_default();
+
+        let eyes = options.eyes.unwrap_or(eyes);
+
+        Self {
+            facing,
+            age: options.age,
+            pose: options.pose.or(mood.pose).unwrap_or(pose),
+            ears: options.ears.or(mood.ears).unwrap_or(ears),
+            eyes,
+            lids: mood.lids,
+            shine: options.shine || eyes != EyeColor::Black,
+            tail_up: mood.tail_up,
+            tilted: mood.tilted,
+            tail,
+            whiskers,
+            toes,
+            golden,
+            legs: options.legs.unwrap_or(legs),
+            belly,
+        }
+    }
+
+    /// Describes the cat in a short sentence, like "a left-facing cat standing
+    /// with green eyes, upright ears, and a curly tail".
+    pub fn describe(&self) -> String {
+        let facing = match self.facing {
+            Facing::Left => "left",
+            Facing::Right => "right",
+        };
+
+        let golden = if self.golden { "golden " } else { "" };
+
+        let noun = match self.age {
+            Age::Adult => "cat",
+            Age::Kitten => "kitten",
+        };
+
+        let pose = match self.pose {
+            Pose::Standing => "standing",
+            Pose::Sitting => "sitting",
+        };
+
+        let eyes = match self.eyes {
+            EyeColor::Black => "black",
+            EyeColor::Green => "green",
+            EyeColor::Amber => "amber",
+            EyeColor::Blue => "blue",
+        };
+
+        let lids = match self.lids {
+            Lids::Open => "",
+            Lids::Narrowed => "narrowed ",
+            Lids::Closed => "closed ",
+        };
+
+        let tilted = if self.tilted { "a tilted head, " } else { "" };
+
+        let held = if self.tail_up { " held up" } else { "" };
+
+        let ears = match self.ears {
+            Ears::Upright => "upright",
+            Ears::Folded => "folded",
+        };
+
+        let tail = match self.tail {
+            Tail::Straight => "straight",
+            Tail::Long => "very long straight",
+            Tail::Curly => "curly",
+            Tail::Curved => "curved",
+        };
+
+        let legs = match self.legs {
+            Legs::Three => "three-legged ",
+            Legs::Four => "",
+        };
+
+        let belly = if self.belly { "a light belly, " } else { "" };
+
+        format!("a {facing}-facing {golden}{legs}{noun} {pose} with {lids}{eyes} eyes, {ears} ears, {tilted}{belly}and a {tail} tail{held}")
+    }
+}
+
+/// Picks the features for every cat that would be drawn with the given
+/// options and seed, without drawing anything.
+///
+/// This uses a different part of the seed than the shapes do, so it's cheap to
+/// figure out what a seed's cats look like.
+pub fn roll_features(options: &CatOptions, seed: u64, config: &Config) -> Result<Vec<CatFeatures>, CanvasError> {
+    canvas_size(options, config)?;
+
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    rng.set_stream(1);
+
+    Ok((0..options.count).map(|_| CatFeatures::roll(&mut rng, options, config)).collect())
+}
+
+/// How many legs a cat has.
+///
+/// This can be given as either a number (`3`) or a string (`"3"`), since query
+/// strings only have strings but JSON bodies have real numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(into = "u8", try_from = "LegCount")]
+pub enum Legs {
+    /// A tripod cat, missing one of its back legs.
+    Three,
+    Four,
+}
+
+/// A number of legs, as written in a request.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LegCount {
+    Number(u8),
+    Text(String),
+}
+
+impl TryFrom<LegCount> for Legs {
+    type Error = String;
+
+    fn try_from(count: LegCount) -> Result<Self, Self::Error> {
+        match count {
+            LegCount::Number(3) => Ok(Legs::Three),
+            LegCount::Number(4) => Ok(Legs::Four),
+            LegCount::Text(text) if text == "3" => Ok(Legs::Three),
+            LegCount::Text(text) if text == "4" => Ok(Legs::Four),
+            LegCount::Number(legs) => Err(format!("a cat can't have {legs} legs")),
+            LegCount::Text(legs) => Err(format!("a cat can't have {legs} legs")),
+        }
+    }
+}
+
+impl From<Legs> for u8 {
+    fn from(legs: Legs) -> Self {
+        match legs {
+            Legs::Three => 3,
+            Legs::Four => 4,
+        }
+    }
+}
+
+/// Which way a cat is looking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Facing {
+    Left,
+    Right,
+}
+
+/// The shape of a cat's tail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Tail {
+    /// A short straight line.
+    Straight,
+    /// A very long straight line.
+    Long,
+    /// A cubic curve, which sometimes curls back on itself.
+    Curly,
+    /// A quadratic curve.
+    Curved,
+}
+
+/// Why a canvas couldn't be made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CanvasError {
+    /// The canvas would have no pixels or no cats.
+    Empty,
+    /// The canvas would use more than the configured maximum number of pixels.
+    TooBig,
+}
+
+impl std::fmt::Display for CanvasError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            CanvasError::Empty => "the size, scale, and count must be at least 1",
+            CanvasError::TooBig => "the image would be too big",
+        })
+    }
+}
+
+impl std::error::Error for CanvasError {}
+
+/// Returns the size of the canvas for a cat with the given options.
+/// 
+/// The canvas is sized according to the config and the scale, and is checked
+/// against the configured maximum number of pixels (counting every cat drawn
+/// on it) so it can be rejected before anything is allocated.
+fn canvas_size(options: &CatOptions, config: &Config) -> Result<(i32, i32), CanvasError> {
+    if options.scale == 0 || options.count == 0 || config.width <= 0 || config.height <= 0 {
+        return Err(CanvasError::Empty);
+    }
+
+    let width = (config.width as u64).saturating_mul(options.scale as u64);
+    let height = (config.height as u64).saturating_mul(options.scale as u64);
+
+    let pixels = width.saturating_mul(height).saturating_mul(options.count as u64);
+    if pixels > config.max_pixels {
+        return Err(CanvasError::TooBig);
+    }
+
+    Ok((
+        width.try_into().map_err(|_| CanvasError::TooBig)?,
+        height.try_into().map_err(|_| CanvasError::TooBig)?,
+    ))
+}
+
+/// Runs `draw` on a blank canvas of the given size.
+/// 
+/// Canvases of the configured size are reused between renders on the same
+/// thread rather than allocating a new pixel buffer every time. They're
+/// cleared to transparent and have their transform reset before each use.
+fn with_canvas<T>(config: &Config, width: i32, height: i32, draw: impl FnOnce(&mut DrawTarget) -> T) -> T {
+    thread_local! {
+        static CANVAS: Cell<Option<DrawTarget>> = const { Cell::new(None) };
+    }
+
+    // Unusual sizes aren't worth keeping around
+    if (width, height) != (config.width, config.height) {
+        return draw(&mut DrawTarget::new(width, height));
+    }
+
+    let mut dt = CANVAS.take()
+        .filter(|dt| (dt.width(), dt.height()) == (width, height))
+        .unwrap_or_else(|| DrawTarget::new(width, height));
+
+    dt.clear(SolidSource { r: 0, g: 0, b: 0, a: 0 });
+    dt.set_transform(&Transform::identity());
+
+    let result = draw(&mut dt);
+
+    CANVAS.set(Some(dt));
+
+    result
+}
+
+/// Turns a name into a seed, so the same name always gets the same cat.
+/// 
+/// This uses 64-bit FNV-1a, which is stable across versions and platforms
+/// (unlike the standard library's hasher).
+pub fn name_seed(name: &str) -> u64 {
+    name.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Turns a date into a seed, so every day has its own cat of the day.
+pub fn daily_seed(date: NaiveDate) -> u64 {
+    name_seed(&format!("daily:{date}"))
+}
+
+/// A theme that every cat made with it shares, set with `themed=`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Theme {
+    /// Every cat made for the same day (see [CatOptions::date]) is tinted the
+    /// same color, so each window's cats look like they're related.
+    Daily,
+}
+
+/// Returns the color that cats made on the given day are tinted (see
+/// [Theme::Daily]). It's a bright color with a hue picked by the date.
+pub fn daily_tint(date: NaiveDate) -> SolidSource {
+    let hue = (daily_seed(date) % 360) as f32 / 60.;
+
+    // Each channel ramps up and down across the color wheel
+    let channel = |offset: f32| {
+        let distance = ((hue + offset) % 6. - 3.).abs();
+        ((distance - 1.).clamp(0., 1.) * 255.) as u8
+    };
+
+    SolidSource { r: channel(0.), g: channel(4.), b: channel(2.), a: 0xff }
+}
+
+/// How old a cat is, which changes its proportions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Age {
+    #[default]
+    Adult,
+    /// A kitten, with a bigger head, bigger eyes, and shorter legs.
+    Kitten,
+}
+
+impl Age {
+    /// The multiplier for the size of the head.
+    fn head(self) -> f32 {
+        match self {
+            Age::Adult => 1.,
+            Age::Kitten => 1.25,
+        }
+    }
+
+    /// The multiplier for the size of the eyes, on top of the head.
+    fn eyes(self) -> f32 {
+        match self {
+            Age::Adult => 1.,
+            Age::Kitten => 1.4,
+        }
+    }
+
+    /// The multiplier for the length of the legs.
+    fn legs(self) -> f32 {
+        match self {
+            Age::Adult => 1.,
+            Age::Kitten => 0.7,
+        }
+    }
+}
+
+/// A finished cat (or cats).
+#[derive(Debug, Clone)]
+pub struct Cat {
+    /// The image, in the requested [Format] (or PNG if it couldn't be encoded
+    /// and the [error image](error_image) was used instead).
+    pub image: Vec<u8>,
+
+    /// The format the image is actually in.
+    pub format: Format,
+
+    /// What each cat looks like.
+    pub features: Vec<CatFeatures>,
+
+    /// How long it took to draw and encode the image.
+    pub render_time: Duration,
+
+    /// The part of the image the cats were drawn in (not counting anything
+    /// behind them, like a scene or rainbow), or `None` if they're entirely off
+    /// the canvas. It comes from the shapes rather than the pixels, so it can
+    /// be a few pixels bigger than the cats.
+    pub bounds: Option<Bounds>,
+}
+
+/// A rectangle of pixels in an image, like the one a cat takes up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bounds {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Bounds {
+    /// Finds the pixels that an area touches on a canvas of the given size,
+    /// or `None` if it's entirely off the canvas.
+    fn covering(area: Box2D<f32>, width: i32, height: i32) -> Option<Self> {
+        let clamp = |value: f32, max: i32| value.clamp(0., max as f32) as u32;
+        let (min_x, min_y) = (clamp(area.min.x.floor(), width), clamp(area.min.y.floor(), height));
+        let (max_x, max_y) = (clamp(area.max.x.ceil(), width), clamp(area.max.y.ceil(), height));
+
+        (min_x < max_x && min_y < max_y).then(|| Bounds { x: min_x, y: min_y, width: max_x - min_x, height: max_y - min_y })
+    }
+}
+
+impl Cat {
+    /// Whether any of the cats came out golden.
+    pub fn golden(&self) -> bool {
+        self.features.iter().any(|features| features.golden)
+    }
+}
+
+/// Draws a cat (or [CatOptions::count] cats) from the given seed.
+/// 
+/// Cats are sized relative to the smaller side of the canvas and placed
+/// around its center, so they fit regardless of the aspect ratio.
+///
+/// What each cat looks like is decided by [roll_features] first, and then
+/// the exact shapes and placement come from the rest of the seed.
+pub fn purchase_cat(options: &CatOptions, seed: u64, config: &Config) -> Result<Cat, CanvasError> {
+    let start = Instant::now();
+
+    let (width, height) = canvas_size(options, config)?;
+    let features = roll_features(options, seed, config)?;
+
+    let mut bounds = None;
+    let mut format = options.format;
+    let image = with_canvas(config, width, height, |dt| {
+        bounds = draw_cats(dt, options, &features, seed, config);
+
+        // Return no data if there's an error
+        let metadata = Metadata { seed };
+
+        match options.format {
+            Format::Png => canvas_to_png(dt, Some(&metadata)),
+            Format::Jpeg => canvas_to_jpeg(dt, options.quality, config.dark()),
+            Format::Rgba => Ok(canvas_to_rgba(dt)),
+        }.unwrap_or_else(|err| {
+            error!("Couldn't encode cat {seed}: {err}");
+            format = Format::Png;
+            error_image()
+        })
+    });
+
+    let render_time = start.elapsed();
+
+    Ok(Cat { image, format, features, render_time, bounds })
+}
+
+/// Draws the cats with the given features (and everything around them) on a
+/// blank surface for [purchase_cat], returning the part of it the cats were
+/// drawn in.
+fn draw_cats(dt: &mut impl Surface, options: &CatOptions, features: &[CatFeatures], seed: u64, config: &Config) -> Option<Bounds> {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let tolerance = config.curve_tolerance;
+
+    let mut colors = ColorScheme::configured(config);
+    if let Some(outline) = options.outline {
+        colors.outline = outline.into();
+    }
+
+    if let (Some(Theme::Daily), Some(date)) = (options.themed, options.date) {
+        colors.tint = Some((daily_tint(date), 0.3));
+    }
+
+    let draw = options.aa.draw_options();
+
+    let (width, height) = (dt.width() as f32, dt.height() as f32);
+
+    // The scene has its own randomness so it doesn't change the cats
+    if let Some(scene) = options.scene {
+        draw_scene(dt, &mut ChaCha8Rng::seed_from_u64(!seed), scene, &colors, &draw, tolerance);
+    }
+
+    // The cat was designed for a 400x256 canvas
+    let size = width.min(height) / 256.;
+
+    // Where the cats (but not anything behind them) were drawn
+    let mut covered = None;
+
+    for features in features {
+        // Rotation is centered around zero degrees in a symmetric triangular
+        // distribution.
+        let rotation = rng.gen_range(0.0..180.0) + rng.gen_range(0.0..180.0) - 180.0;
+
+        // Cats are drawn facing left, so right-facing cats are mirrored
+        let facing = match features.facing {
+            Facing::Left => 1.,
+            Facing::Right => -1.,
+        };
+
+        // Scale and rotate the cat around its own center first
+        let turned: Transform = Transform2D::identity()
+            .then_scale(facing * size * (1.1 + rng.gen_range(-0.02..0.02)), size * (1.1 + rng.gen_range(-0.02..0.02)))
+            .then_rotate(Angle::degrees(rotation));
+
+        // Where the cat goes, and how far it can move from there while
+        // staying on the canvas
+        let (turned, (x, wiggle_x), (y, wiggle_y)) = if options.fit {
+            fit(turned, width, height)
+        } else {
+            (
+                turned,
+                (width / 2. - 5. * size, (width / 2. - 130. * size).max(0.01)),
+                (height / 2. - 4. * size, (height / 2. - 83. * size).max(0.01)),
+            )
+        };
+
+        // Generate the transfrom (scale, rotate, translate) for the cat :cat2:
+        let base_transform = turned.then_translate(Vector::new(
+            x + rng.gen_range(-wiggle_x..wiggle_x),
+            y + rng.gen_range(-wiggle_y..wiggle_y),
+        ));
+
+        // The trail goes behind the cat, and isn't random so it doesn't
+        // change the cat
+        if options.style == Some(Style::Rainbow) {
+            draw_rainbow(dt, &base_transform, features.pose, &draw);
+        }
+
+        let mut cat = Extents { surface: &mut *dt, covered };
+
+        // The shadow is the same cat, so it uses a copy of the randomness
+        if options.shadow {
+            draw_shadow(&mut cat, &mut rng.clone(), &base_transform.then_translate(Vector::new(4. * size, 4. * size)), features, &draw, tolerance);
+        }
+
+        if features.golden {
+            draw_cat(&mut cat, &mut rng, &base_transform, features, &ColorScheme::golden(), &draw, tolerance);
+            draw_sparkles(&mut cat, &mut rng, &base_transform, &draw);
+        } else {
+            draw_cat(&mut cat, &mut rng, &base_transform, features, &colors, &draw, tolerance);
+        }
+
+        covered = cat.covered;
+    }
+
+    let bounds = covered.and_then(|covered| Bounds::covering(covered, width as i32, height as i32));
+
+    if options.mark {
+        draw_mark(dt, options.border, size, &colors, &draw, tolerance);
+    }
+
+    draw_border(dt, options.border, &colors, &draw);
+
+    bounds
+}
+
+/// The parts of a [DrawTarget] that cats are drawn with, so they can be drawn
+/// on other things too (like [Extents]).
+pub trait Surface {
+    fn width(&self) -> i32;
+    fn height(&self) -> i32;
+    fn get_transform(&self) -> &Transform;
+    fn set_transform(&mut self, transform: &Transform);
+    fn fill(&mut self, path: &Path, src: &Source, options: &DrawOptions);
+    fn stroke(&mut self, path: &Path, src: &Source, style: &StrokeStyle, options: &DrawOptions);
+    fn push_clip(&mut self, path: &Path);
+    fn pop_clip(&mut self);
+    fn push_layer(&mut self, opacity: f32);
+    fn pop_layer(&mut self);
+}
+
+impl Surface for DrawTarget {
+    fn width(&self) -> i32 {
+        DrawTarget::width(self)
+    }
+
+    fn height(&self) -> i32 {
+        DrawTarget::height(self)
+    }
+
+    fn get_transform(&self) -> &Transform {
+        DrawTarget::get_transform(self)
+    }
+
+    fn set_transform(&mut self, transform: &Transform) {
+        DrawTarget::set_transform(self, transform)
+    }
+
+    fn fill(&mut self, path: &Path, src: &Source, options: &DrawOptions) {
+        DrawTarget::fill(self, path, src, options)
+    }
+
+    fn stroke(&mut self, path: &Path, src: &Source, style: &StrokeStyle, options: &DrawOptions) {
+        DrawTarget::stroke(self, path, src, style, options)
+    }
+
+    fn push_clip(&mut self, path: &Path) {
+        DrawTarget::push_clip(self, path)
+    }
+
+    fn pop_clip(&mut self) {
+        DrawTarget::pop_clip(self)
+    }
+
+    fn push_layer(&mut self, opacity: f32) {
+        DrawTarget::push_layer(self, opacity)
+    }
+
+    fn pop_layer(&mut self) {
+        DrawTarget::pop_layer(self)
+    }
+}
+
+/// Draws on another surface while keeping track of the area the shapes
+/// cover, worked out from their paths (so it doesn't matter what was there
+/// before, or what color they are).
+///
+/// Curves are covered by their control points, so the area can be a little
+/// bigger than the shapes, but never smaller.
+struct Extents<'a, S: Surface> {
+    surface: &'a mut S,
+    /// The area covered so far, in canvas coordinates.
+    covered: Option<Box2D<f32>>,
+}
+
+impl<S: Surface> Extents<'_, S> {
+    /// Adds a path in the current transform to the covered area, with `reach`
+    /// units of room around it for the width of a stroke.
+    fn cover(&mut self, path: &Path, reach: f32) {
+        let transform = *self.surface.get_transform();
+
+        let points = path.ops.iter().flat_map(|op| match *op {
+            PathOp::MoveTo(to) | PathOp::LineTo(to) => vec![to],
+            PathOp::QuadTo(ctrl, to) => vec![ctrl, to],
+            PathOp::CubicTo(ctrl1, ctrl2, to) => vec![ctrl1, ctrl2, to],
+            PathOp::Close => vec![],
+        }).map(|point| transform.transform_point(point));
+
+        // Straight lines make flat boxes, which euclid treats as empty, so
+        // the corners are found by hand
+        let Some(area) = points.fold(None, |area: Option<Box2D<f32>>, point| Some(match area {
+            Some(area) => Box2D::new(area.min.min(point), area.max.max(point)),
+            None => Box2D::new(point, point),
+        })) else {
+            return;
+        };
+
+        // How far the room around the path reaches in canvas pixels
+        let scale = transform.m11.hypot(transform.m12).max(transform.m21.hypot(transform.m22));
+        let area = area.inflate(reach * scale, reach * scale);
+
+        self.covered = Some(match self.covered {
+            Some(covered) => Box2D::new(covered.min.min(area.min), covered.max.max(area.max)),
+            None => area,
+        });
+    }
+}
+
+impl<S: Surface> Surface for Extents<'_, S> {
+    fn width(&self) -> i32 {
+        self.surface.width()
+    }
+
+    fn height(&self) -> i32 {
+        self.surface.height()
+    }
+
+    fn get_transform(&self) -> &Transform {
+        self.surface.get_transform()
+    }
+
+    fn set_transform(&mut self, transform: &Transform) {
+        self.surface.set_transform(transform)
+    }
+
+    fn fill(&mut self, path: &Path, src: &Source, options: &DrawOptions) {
+        self.cover(path, 0.);
+        self.surface.fill(path, src, options)
+    }
+
+    fn stroke(&mut self, path: &Path, src: &Source, style: &StrokeStyle, options: &DrawOptions) {
+        // Square caps stick out diagonally, and miters can stick out up to the
+        // miter limit
+        let reach = match style.join {
+            LineJoin::Miter => style.miter_limit.max(f32::consts::SQRT_2),
+            _ => f32::consts::SQRT_2,
+        };
+
+        self.cover(path, reach * style.width / 2.);
+        self.surface.stroke(path, src, style, options)
+    }
+
+    fn push_clip(&mut self, path: &Path) {
+        self.surface.push_clip(path)
+    }
+
+    fn pop_clip(&mut self) {
+        self.surface.pop_clip()
+    }
+
+    fn push_layer(&mut self, opacity: f32) {
+        self.surface.push_layer(opacity)
+    }
+
+    fn pop_layer(&mut self) {
+        self.surface.pop_layer()
+    }
+}
+
+/// Draws a frame of the given thickness (in pixels) just inside the edge of the
+/// canvas, in the outline color. Nothing is drawn if it's zero.
+fn draw_border(dt: &mut impl Surface, border: u32, colors: &ColorScheme, draw: &DrawOptions) {
+    if border == 0 {
+        return;
+    }
+
+    // Strokes are centered on the path, so half of it would be off the canvas
+    let width = border as f32;
+    let mut pb = PathBuilder::new();
+    pb.rect(width / 2., width / 2., dt.width() as f32 - width, dt.height() as f32 - width);
+
+    dt.set_transform(&Transform::identity());
+    dt.stroke(&pb.finish(), &colors.outline(), &StrokeStyle {
+        width,
+        join: LineJoin::Miter,
+        ..StrokeStyle::default()
+    }, draw);
+}
+
+/// Draws a small, faint paw print in the bottom right corner of the canvas,
+/// inside the border (if there is one). `size` is the same as the cats'.
+fn draw_mark(dt: &mut impl Surface, border: u32, size: f32, colors: &ColorScheme, draw: &DrawOptions, tolerance: f32) {
+    let detail = detail(&Transform::scale(size, size), tolerance);
+    let (x, y) = (
+        dt.width() as f32 - border as f32 - 12. * size,
+        dt.height() as f32 - border as f32 - 11. * size,
+    );
+
+    let mut pb = PathBuilder::new();
+
+    // The pad, with four toes around the top
+    ellipse(&mut pb, x, y + 2. * size, 4. * size, 3.2 * size, detail);
+    pb.close();
+    for (toe_x, toe_y) in [(-5., -3.), (-1.8, -5.5), (1.8, -5.5), (5., -3.)] {
+        ellipse(&mut pb, x + toe_x * size, y + toe_y * size, 1.5 * size, 1.9 * size, detail);
+        pb.close();
+    }
+
+    dt.set_transform(&Transform::identity());
+    dt.fill(&pb.finish(), &colors.outline(), &DrawOptions { alpha: 0.3, ..*draw });
+}
+
+/// Shrinks a cat that's been turned (see [CatOptions::fit]) until it fits on a
+/// canvas of the given size, returning it along with where it goes and how far
+/// it can move from there on each axis while staying on the canvas.
+fn fit(turned: Transform, width: f32, height: f32) -> (Transform, (f32, f32), (f32, f32)) {
+    // Rotated cats take up a different part of the canvas, so find the room
+    // around wherever the cat ends up after turning
+    let [(left, top), (right, bottom)] = CAT_BOUNDS;
+    let corners = [(left, top), (right, top), (left, bottom), (right, bottom)]
+        .map(|(x, y)| turned.transform_point(Point::new(x, y)));
+
+    let span = |sides: [f32; 4]| sides.into_iter().fold((f32::MAX, f32::MIN), |(min, max), side| (min.min(side), max.max(side)));
+    let ((min_x, max_x), (min_y, max_y)) = (span(corners.map(|corner| corner.x)), span(corners.map(|corner| corner.y)));
+
+    // Cats turned on their side are too tall for wide canvases, so they're
+    // shrunk until they fit
+    let shrink = (width / (max_x - min_x)).min(height / (max_y - min_y)).min(1.);
+
+    let room = |canvas: f32, min: f32, max: f32| (
+        (canvas - (min + max) * shrink) / 2.,
+        ((canvas - (max - min) * shrink) / 2.).max(0.01),
+    );
+
+    (turned.then_scale(shrink, shrink), room(width, min_x, max_x), room(height, min_y, max_y))
+}
+
+/// Roughly the top left and bottom right corners of a cat (without a long
+/// tail), in the cat's coordinate space (see [draw_cat]).
+const CAT_BOUNDS: [(f32, f32); 2] = [(-98., -98.), (140., 105.)];
+
+/// Like [purchase_cat], but returns the width, height, and raw RGBA pixels of
+/// the image instead of encoding it (see [Format::Rgba]).
+pub fn purchase_cat_rgba(options: &CatOptions, seed: u64, config: &Config) -> Result<(u32, u32, Vec<u8>), CanvasError> {
+    let (width, height) = canvas_size(options, config)?;
+    let cat = purchase_cat(&CatOptions { format: Format::Rgba, ..options.clone() }, seed, config)?;
+
+    Ok((width as u32, height as u32, cat.image))
+}
+
+/// Like [purchase_cat], but draws the cats as an SVG document of the same size
+/// instead of an image. The same seed and options draw the same cats.
+pub fn purchase_cat_svg(options: &CatOptions, seed: u64, config: &Config) -> Result<String, CanvasError> {
+    let (width, height) = canvas_size(options, config)?;
+    let features = roll_features(options, seed, config)?;
+
+    let mut svg = Svg::new(width, height);
+    draw_cats(&mut svg, options, &features, seed, config);
+
+    Ok(svg.finish())
+}
+
+/// Draws a soft shadow in the shape of a cat, with the same features and
+/// randomness as the cat it's for. Draw the cat on top of it afterwards.
+fn draw_shadow(dt: &mut impl Surface, rng: &mut impl Rng, base: &Transform, features: &CatFeatures, draw: &DrawOptions, tolerance: f32) {
+    // Colorful details would show through the shadow
+    let features = CatFeatures { eyes: EyeColor::Black, belly: false, shine: false, ..*features };
+
+    let black = SolidSource { r: 0, g: 0, b: 0, a: 0xff };
+    let colors = ColorScheme { outline: black, min: 0, max: 0, tint: None };
+
+    // The cat is drawn solid and faded all at once, so overlapping shapes don't
+    // make the shadow darker
+    dt.push_layer(0.25);
+    draw_cat(dt, rng, base, &features, &colors, draw, tolerance);
+    dt.pop_layer();
+}
+
+/// Draws a few little four-pointed stars around a golden cat, in the cat's
+/// coordinate space (see [draw_cat]).
+fn draw_sparkles(dt: &mut impl Surface, rng: &mut impl Rng, base: &Transform, draw: &DrawOptions) {
+    let sparkles = {
+        let mut pb = PathBuilder::new();
+
+        for _ in 0..rng.gen_range(3..=5) {
+            // Somewhere around the cat, not on it
+            let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+            let (x, y) = (angle.cos() * rng.gen_range(95.0..115.0), angle.sin() * rng.gen_range(65.0..80.0));
+            let (long, short) = (rng.gen_range(7.0..11.0), 2.);
+
+            pb.move_to(x, y - long);
+            pb.line_to(x + short, y - short);
+            pb.line_to(x + long, y);
+            pb.line_to(x + short, y + short);
+            pb.line_to(x, y + long);
+            pb.line_to(x - short, y + short);
+            pb.line_to(x - long, y);
+            pb.line_to(x - short, y - short);
+            pb.close();
+        }
+
+        pb.finish()
+    };
+
+    dt.set_transform(base);
+    dt.fill(&sparkles, &Source::Solid(SolidSource { r: 0xff, g: 0xf0, b: 0x90, a: 0xff }), draw);
+    dt.stroke(&sparkles, &Source::Solid(ColorScheme::golden().outline), &StrokeStyle {
+        join: LineJoin::Round,
+        width: 1.5,
+        ..StrokeStyle::default()
+    }, draw);
+}
+
+/// Something extra drawn with the cats, for fun.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Style {
+    /// A wavy rainbow trail streaming out behind the cat, like it's flying
+    /// through space.
+    Rainbow,
+}
+
+/// Draws a rainbow trail from a cat's tail off towards the edge of the canvas,
+/// in the cat's coordinate space (see [draw_cat]).
+fn draw_rainbow(dt: &mut impl Surface, base: &Transform, pose: Pose, draw: &DrawOptions) {
+    const COLORS: [(u8, u8, u8); 6] = [
+        (0xff, 0x3b, 0x30),
+        (0xff, 0x95, 0x00),
+        (0xff, 0xe0, 0x20),
+        (0x4c, 0xd9, 0x64),
+        (0x34, 0x8c, 0xff),
+        (0x9b, 0x59, 0xd6),
+    ];
+
+    // How thick each band is, and how far each wave goes
+    const BAND: f32 = 6.;
+    const WAVE: f32 = 40.;
+
+    let (x, y) = pose.tail();
+    let top = y - BAND * COLORS.len() as f32 / 2.;
+
+    dt.set_transform(base);
+
+    for (i, (r, g, b)) in COLORS.into_iter().enumerate() {
+        let y = top + BAND * (i as f32 + 0.5);
 
-use crate::{HOUR, MINUTE};
+        // Bob up and down a bit, going far enough to leave any canvas
+        let mut pb = PathBuilder::new();
+        pb.move_to(x, y);
+        for wave in 0..8 {
+            let start = x + wave as f32 * WAVE;
+            let bob = if wave % 2 == 0 { -BAND } else { BAND };
+            pb.quad_to(start + WAVE / 2., y + bob, start + WAVE, y);
+        }
 
-/// Draws the "come back at 2:22" text, returning a PNG.
-pub fn out_of_stock() -> Vec<u8> {
-    let mut dt = DrawTarget::new(400, 256);
+        dt.stroke(&pb.finish(), &Source::Solid(SolidSource { r, g, b, a: 0xff }), &StrokeStyle {
+            cap: LineCap::Butt,
+            width: BAND + 0.5,
+            ..StrokeStyle::default()
+        }, draw);
+    }
+}
 
-    // Get the font
-    static FONT: OnceLock<Handle> = OnceLock::new();
-    let font = FONT.get_or_init(|| {
-        SystemSource::new()
-        .select_by_postscript_name("DejaVuSans").unwrap()
-    });
+/// A backdrop for cats to sit in front of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scene {
+    /// A rug on the floor.
+    Rug,
+    /// A window on the wall, above the floor.
+    Window,
+}
 
-    let mut rng = rand::thread_rng();
-
-    // Pick the text and draw it
-    let (text, x, y) = if rng.gen_bool(0.5) {
-    (
-            format!("come back at {HOUR}:{MINUTE:0>2}"),
-            rng.gen_range(8.0..194.0),
-            rng.gen_range(25.0..248.0),
-        )
-    } else {
-        (
-            format!("torna a {HOUR}:{MINUTE:0>2}"),
-            rng.gen_range(8.0..260.0),
-            rng.gen_range(25.0..248.0),
-        )
-    };
+/// Draws a scene across the whole canvas, in canvas coordinates.
+fn draw_scene(dt: &mut impl Surface, rng: &mut impl Rng, scene: Scene, colors: &ColorScheme, draw: &DrawOptions, tolerance: f32) {
+    let (width, height) = (dt.width() as f32, dt.height() as f32);
 
-    // The text can't be rotated because of a bug with raqote.
-    // Hopefully this will change!
+    // Keep outlines the same thickness as the cat's
+    let style = StrokeStyle {
+        width: stroke().width * width.min(height) / 256.,
+        ..stroke().clone()
+    };
 
-    dt.draw_text(&font.load().unwrap(), 24., &text, Point::new(x, y), &BLACK, &DRAW);
+    match scene {
+        Scene::Rug => {
+            let mut pb = PathBuilder::new();
+            ellipse(&mut pb, width / 2., height * 0.8, width * 0.42, height * 0.15, Detail { scale: 1., tolerance });
+            pb.close();
+            let rug = pb.finish();
 
-    canvas_to_png(dt).unwrap_or_else(|_| Vec::new())
+            dt.stroke(&rug, &colors.outline(), &style, draw);
+            dt.fill(&rug, &colors.fill(rng), draw);
+        }
+        Scene::Window => {
+            let (x, y, w, h) = (width * 0.3, height * 0.08, width * 0.4, height * 0.5);
+
+            let mut pb = PathBuilder::new();
+            pb.rect(x, y, w, h);
+            let window = pb.finish();
+
+            // The frame splits the window into four panes
+            let mut pb = PathBuilder::new();
+            pb.move_to(x + w / 2., y);
+            pb.line_to(x + w / 2., y + h);
+            pb.move_to(x, y + h / 2.);
+            pb.line_to(x + w, y + h / 2.);
+            let frame = pb.finish();
+
+            // Where the wall meets the floor
+            let mut pb = PathBuilder::new();
+            pb.move_to(0., height * 0.75);
+            pb.line_to(width, height * 0.75);
+            let floor = pb.finish();
+
+            dt.fill(&window, &Source::Solid(SolidSource { r: 0xbf, g: 0xe3, b: 0xff, a: 0xff }), draw);
+            dt.stroke(&window, &colors.outline(), &style, draw);
+            dt.stroke(&frame, &colors.outline(), &style, draw);
+            dt.stroke(&floor, &colors.outline(), &style, draw);
+        }
+    }
 }
 
-/// Draws a cat, returning a PNG.
-pub fn purchase_cat() -> Vec<u8> {
-    let mut rng = rand::thread_rng();
+/// The shape of a cat's ears.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Ears {
+    /// Pointy ears sticking straight up.
+    Upright,
+    /// Ears with the tips folded down, like a Scottish fold.
+    Folded,
+}
 
-    let mut dt = DrawTarget::new(400, 256);
+/// The color of a cat's eyes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EyeColor {
+    /// Plain eyes with no visible iris, like the original cats.
+    Black,
+    Green,
+    Amber,
+    Blue,
+}
 
-    // Rotation is centered around zero degrees in a symmetric triangular
-    // distribution.
-    let rotation = rng.gen_range(0.0..180.0) + rng.gen_range(0.0..180.0) - 180.0;
+/// How open a cat's eyes are.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Lids {
+    /// Wide open, like every cat used to have.
+    #[default]
+    Open,
+    /// Half closed, with a flat lid.
+    Narrowed,
+    /// Shut.
+    Closed,
+}
 
-    // Generate the transfrom (scale, rotate, translate) for the cat :cat2:
-    let base_transform = Transform2D::identity()
-        .then_scale(1.1 + rng.gen_range(-0.02..0.02), 1.1 + rng.gen_range(-0.02..0.02))
-        .then_rotate(Angle::degrees(rotation))
-        .then_translate(Vector::new(
-            195. + rng.gen_range(-70.0..70.0),
-            124. + rng.gen_range(-45.0..45.0),
-        ));
+impl EyeColor {
+    /// Every eye color, in order.
+    pub const ALL: [EyeColor; 4] = [EyeColor::Black, EyeColor::Green, EyeColor::Amber, EyeColor::Blue];
 
-    draw_cat(&mut dt, &base_transform);
+    /// The color of the iris, or `None` if the whole eye is the pupil.
+    fn iris(self) -> Option<Source<'static>> {
+        let (r, g, b) = match self {
+            EyeColor::Black => return None,
+            EyeColor::Green => (0x6a, 0xb0, 0x4c),
+            EyeColor::Amber => (0xe0, 0xa0, 0x30),
+            EyeColor::Blue => (0x5a, 0x9b, 0xd8),
+        };
 
-    // Return no data if there's an error
-    canvas_to_png(dt).unwrap_or_else(|_| Vec::new())
+        Some(Source::Solid(SolidSource { r, g, b, a: 0xff }))
+    }
 }
 
-/// Draws the head of the cat around `0, 0`.
-fn draw_head(dt: &mut DrawTarget) {
-    let mut rng = rand::thread_rng();
+/// Draws the head of a cat with the given features centered on `0, 0` in the
+/// draw target's current transform, using `rng` for the exact shapes.
+///
+/// The head is about 50 units across (without the whiskers), with the ears
+/// pointing up towards negative y.
+pub fn draw_head(dt: &mut impl Surface, rng: &mut impl Rng, features: &CatFeatures, colors: &ColorScheme, draw: &DrawOptions, tolerance: f32) {
+    let detail = detail(dt.get_transform(), tolerance);
 
     let ears = {
         let mut pb = PathBuilder::new();
 
-        let points = (
-            (6., -25.),
-            (21. + rng.gen_range(-2.0..2.0), -36. + rng.gen_range(-2.0..2.0)),
-            (21., -17.)
-        );
+        let (x, y) = (21. + rng.gen_range(-2.0..2.0), -36. + rng.gen_range(-2.0..2.0));
+
+        match features.ears {
+            Ears::Upright => mirrored(&mut pb, &[
+                (6., -25.),
+                (x, y),
+                (21., -17.)
+            ], true),
+            // The top of the ear bends over, so the tip points down and out
+            Ears::Folded => mirrored(&mut pb, &[
+                (6., -25.),
+                (x - 7., y + 4.),
+                (x + 1., y + 15.),
+                (x - 4., y + 13.),
+                (21., -17.)
+            ], true),
+        }
 
-        pb.move_to(points.0.0, points.0.1);
-        pb.line_to(points.1.0, points.1.1);
-        pb.line_to(points.2.0, points.2.1);
-        pb.close();
+        pb.finish()
+    };
 
-        pb.move_to(-points.0.0, points.0.1);
-        pb.line_to(-points.1.0, points.1.1);
-        pb.line_to(-points.2.0, points.2.1);
-        pb.close();
+    let whiskers = {
+        let mut pb = PathBuilder::new();
+
+        let count = features.whiskers;
+        for i in 0..count {
+            let spread = i as f32 - (count - 1) as f32 / 2.;
+
+            mirrored(&mut pb, &[
+                (9., 7. + spread * 2.),
+                (33. + rng.gen_range(-3.0..3.0), 5. + spread * 7. + rng.gen_range(-1.0..1.0)),
+            ], false);
+        }
 
         pb.finish()
     };
 
     let head = {
         let mut pb = PathBuilder::new();
-        ellipse(&mut pb, 0., 0., 25., 24.);
+        ellipse(&mut pb, 0., 0., 25., 24., detail);
         pb.close();
 
         pb.finish()
     };
 
-    let eyes = {
-        let mut pb = PathBuilder::new();
+    let r = rng.gen_range(2.7..3.3) * features.age.eyes();
+
+    // Narrowed eyes are squashed, with a flat lid over the top
+    let squash = if features.lids == Lids::Narrowed { 0.5 } else { 1. };
 
-        let r = rng.gen_range(2.7..3.3);
+    let eyes = |r: f32| {
+        let mut pb = PathBuilder::new();
 
-        ellipse(&mut pb, 9., -7., r, r);
-        ellipse(&mut pb, -9., -7., r, r);
+        ellipse(&mut pb, 9., -7., r, r * squash, detail);
+        ellipse(&mut pb, -9., -7., r, r * squash, detail);
         pb.close();
 
         pb.finish()
     };
 
+    // The light comes from the top left
+    let shine = {
+        let mut pb = PathBuilder::new();
+
+        for x in [9., -9.] {
+            ellipse(&mut pb, x - r * 0.35, -7. - r * 0.35 * squash, r * 0.28, r * 0.28 * squash, detail);
+            pb.close();
+        }
+
+        pb.finish()
+    };
+
+    let lids = {
+        let mut pb = PathBuilder::new();
+
+        match features.lids {
+            Lids::Open => {}
+            Lids::Narrowed => mirrored(&mut pb, &[
+                (9. - r * 1.3, -7. - r * 0.3),
+                (9. + r * 1.3, -7. - r * 0.7),
+            ], false),
+            // Closed eyes are just a curve, like they're smiling in their sleep
+            Lids::Closed => for x in [9., -9.] {
+                pb.move_to(x - r, -7.);
+                pb.quad_to(x, -7. + r, x + r, -7.);
+            },
+        }
+
+        pb.finish()
+    };
+
     let nose = {
         let mut pb = PathBuilder::new();
 
@@ -134,51 +1662,200 @@ fn draw_head(dt: &mut DrawTarget) {
         pb.finish()
     };
 
-    dt.stroke(&ears, &BLACK, &stroke(), &DRAW);
-    dt.fill(&ears, &random_color(), &DRAW);
+    dt.stroke(&ears, &colors.outline(), stroke(), draw);
+    dt.fill(&ears, &colors.fill(rng), draw);
     
-    dt.stroke(&head, &BLACK, &stroke(), &DRAW);
-    dt.fill(&head, &random_color(), &DRAW);
+    dt.stroke(&head, &colors.outline(), stroke(), draw);
+    dt.fill(&head, &colors.fill(rng), draw);
+
+    match (features.lids, features.eyes.iris()) {
+        (Lids::Closed, _) => {}
+        (_, Some(iris)) => {
+            dt.fill(&eyes(r), &iris, draw);
+            dt.fill(&eyes(r * 0.55), &colors.outline(), draw);
+        }
+        (_, None) => dt.fill(&eyes(r), &colors.outline(), draw),
+    }
+
+    if features.shine && features.lids != Lids::Closed {
+        dt.fill(&shine, &Source::Solid(SolidSource { r: 0xff, g: 0xff, b: 0xff, a: 0xff }), draw);
+    }
+
+    dt.stroke(&lids, &colors.outline(), &StrokeStyle {
+        cap: LineCap::Round,
+        width: 1.5,
+        ..StrokeStyle::default()
+    }, draw);
+
+    dt.fill(&nose, &colors.outline(), draw);
+
+    dt.stroke(&whiskers, &colors.outline(), &StrokeStyle {
+        cap: LineCap::Round,
+        width: 1.,
+        ..StrokeStyle::default()
+    }, draw);
+}
+
+/// How a cat is feeling, which decides several of its features at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mood {
+    /// Open eyes, with the tail held up.
+    Happy,
+    /// Folded ears and narrowed eyes.
+    Grumpy,
+    /// Sitting down with closed eyes.
+    Sleepy,
+    /// Upright ears, with the head tilted.
+    Curious,
+}
+
+/// The features a mood decides. Anything that's `None` is left up to chance.
+#[derive(Default)]
+struct MoodFeatures {
+    pose: Option<Pose>,
+    ears: Option<Ears>,
+    lids: Lids,
+    tail_up: bool,
+    tilted: bool,
+}
+
+impl Mood {
+    /// The features a cat with this mood has.
+    fn features(self) -> MoodFeatures {
+        match self {
+            Mood::Happy => MoodFeatures { lids: Lids::Open, tail_up: true, ..MoodFeatures::default() },
+            Mood::Grumpy => MoodFeatures { ears: Some(Ears::Folded), lids: Lids::Narrowed, ..MoodFeatures::default() },
+            Mood::Sleepy => MoodFeatures { pose: Some(Pose::Sitting), lids: Lids::Closed, ..MoodFeatures::default() },
+            Mood::Curious => MoodFeatures { ears: Some(Ears::Upright), tilted: true, ..MoodFeatures::default() },
+        }
+    }
+}
+
+/// How a cat is holding itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Pose {
+    /// On all four legs, with the body flat.
+    Standing,
+    /// On its haunches, with the body upright, the front legs straight down,
+    /// and the back legs tucked underneath.
+    Sitting,
+}
+
+impl Pose {
+    /// Where the tail starts.
+    fn tail(self) -> (f32, f32) {
+        match self {
+            Pose::Standing => (60., 0.),
+            Pose::Sitting => (40., 46.),
+        }
+    }
+
+    /// Where the body goes.
+    fn body(self) -> Transform {
+        match self {
+            Pose::Standing => Transform::identity(),
+            Pose::Sitting => Transform::rotation(Angle::degrees(40.)).then_translate(Vector::new(0., 12.)),
+        }
+    }
+
+    /// Where the neck goes.
+    fn neck(self) -> Transform {
+        match self {
+            Pose::Standing => Transform::rotation(Angle::degrees(-30.)).then_translate(Vector::new(-45., -19.)),
+            Pose::Sitting => Transform::rotation(Angle::degrees(-10.)).then_translate(Vector::new(-40., -30.)),
+        }
+    }
 
-    dt.fill(&eyes, &BLACK, &DRAW);
+    /// Where the center of the head goes.
+    fn head(self) -> (f32, f32) {
+        match self {
+            Pose::Standing => (-59., -44.),
+            Pose::Sitting => (-50., -56.),
+        }
+    }
+
+    /// Where the center of each leg goes, along with its rotation in degrees,
+    /// or `None` for legs the cat doesn't have.
+    fn legs(self, legs: Legs) -> [Option<((f32, f32), f32)>; 4] {
+        let [front, middle, third, back] = self.all_legs().map(Some);
+
+        match (legs, self) {
+            (Legs::Four, _) => [front, middle, third, back],
+            // The other back leg moves in a bit to hold the cat up
+            (Legs::Three, Pose::Standing) => [front, middle, None, Some(((36., 24.), -10.))],
+            (Legs::Three, Pose::Sitting) => [front, middle, None, back],
+        }
+    }
 
-    dt.fill(&nose, &BLACK, &DRAW);
+    /// Where the center of each of four legs goes, along with its rotation in
+    /// degrees.
+    fn all_legs(self) -> [((f32, f32), f32); 4] {
+        match self {
+            Pose::Standing => [
+                ((-45., 21.), 20.),
+                ((-25., 26.), 5.),
+                (( 25., 26.), -5.),
+                (( 45., 21.), -20.),
+            ],
+            // The back legs lie flat with their paws pointing forwards
+            Pose::Sitting => [
+                ((-42., 32.), 3.),
+                ((-26., 34.), -3.),
+                (( 14., 56.), 85.),
+                (( 32., 58.), 90.),
+            ],
+        }
+    }
 }
 
-/// Draws the cat around the base transform.
-fn draw_cat(dt: &mut DrawTarget, base: &Transform) {
-    let mut rng = rand::thread_rng();
+/// Draws a whole cat with the given features, using `rng` for the exact
+/// shapes.
+///
+/// The cat is drawn in the coordinate space of `base`, where the middle of
+/// the body is at `0, 0` and the cat is about 200 units wide and 120 units
+/// tall, facing towards negative x (regardless of [CatFeatures::facing], which
+/// is up to `base`). Put the cat somewhere on the canvas (and scale, rotate, or
+/// mirror it) by changing `base`. The draw target is left with `base` as its
+/// transform.
+///
+/// Every shape is drawn with `draw`, which decides things like antialiasing.
+pub fn draw_cat(dt: &mut impl Surface, rng: &mut impl Rng, base: &Transform, features: &CatFeatures, colors: &ColorScheme, draw: &DrawOptions, tolerance: f32) {
+    let pose = features.pose;
+    let detail = detail(base, tolerance);
 
     let tail = {
         let mut pb = PathBuilder::new();
 
-        let (x, y) = (60., 0.);
+        let (x, y) = pose.tail();
         
         let sign = if rng.gen::<bool>() { 1. } else { -1. };
 
         pb.move_to(x, y);
 
-        // 5% chance for a straight line tail
-        if rng.gen_ratio(1, 20) {
-            // Additional 10% chance for a very long straight tail
-            let scale = if rng.gen_ratio(1, 10) { 5. }
-                else { 1. };
-            pb.line_to(x + scale*rng.gen_range(40.0..70.0), y + scale*rng.gen_range(-30.0..30.0));
-        } else if rng.gen::<bool>() { // Otherwise, 50% chance for a cubic tail
-            let scale = rng.gen_range(2.5..3.5);
-
-            pb.cubic_to(
-                x + scale*rng.gen_range(12.0..17.0), y + scale*sign*rng.gen_range(0.0..5.0),
-                x + scale*rng.gen_range(-5.0..0.0), y + scale*sign*rng.gen_range(10.0..15.0),
-                x + scale*rng.gen_range(15.0..25.0), y + scale*sign*rng.gen_range(5.0..15.0),
-            );
-        } else { // And a 50% chance for a quadratic tail
-            let scale = rng.gen_range(3.0..4.0);
-
-            pb.quad_to(
-                x + scale*rng.gen_range(12.0..17.0), y + scale*sign*rng.gen_range(0.0..5.0),
-                x + scale*rng.gen_range(5.0..20.0), y + scale*sign*rng.gen_range(12.0..17.0),
-            );
+        match features.tail {
+            Tail::Straight | Tail::Long => {
+                let scale = if features.tail == Tail::Long { 5. } else { 1. };
+                pb.line_to(x + scale*rng.gen_range(40.0..70.0), y + scale*rng.gen_range(-30.0..30.0));
+            }
+            Tail::Curly => {
+                let scale = rng.gen_range(2.5..3.5);
+
+                pb.cubic_to(
+                    x + scale*rng.gen_range(12.0..17.0), y + scale*sign*rng.gen_range(0.0..5.0),
+                    x + scale*rng.gen_range(-5.0..0.0), y + scale*sign*rng.gen_range(10.0..15.0),
+                    x + scale*rng.gen_range(15.0..25.0), y + scale*sign*rng.gen_range(5.0..15.0),
+                );
+            }
+            Tail::Curved => {
+                let scale = rng.gen_range(3.0..4.0);
+
+                pb.quad_to(
+                    x + scale*rng.gen_range(12.0..17.0), y + scale*sign*rng.gen_range(0.0..5.0),
+                    x + scale*rng.gen_range(5.0..20.0), y + scale*sign*rng.gen_range(12.0..17.0),
+                );
+            }
         }
 
         pb.finish()
@@ -189,67 +1866,129 @@ fn draw_cat(dt: &mut DrawTarget, base: &Transform) {
 
         let r = rng.gen_range(11.0..16.0);
 
-        pb.rect(-r, -r, r*2., r*2.);
-        pb.close();
+        // Rounded off so it doesn't look like a box when the cat is rotated
+        rounded_rect(&mut pb, -r, -r, r*2., r*2., r*0.6);
 
         pb.finish()
     };
 
+    let (body_width, body_height) = (rng.gen_range(55.0..66.0), rng.gen_range(25.0..30.0));
+
     let body = {
         let mut pb = PathBuilder::new();
-        ellipse(&mut pb, 0., 0., rng.gen_range(55.0..66.0), rng.gen_range(25.0..30.0));
+        ellipse(&mut pb, 0., 0., body_width, body_height, detail);
         pb.close();
 
         pb.finish()
     };
 
+    // The lower part of the body, which gets cut off by the body's outline
+    let belly = features.belly.then(|| {
+        let mut pb = PathBuilder::new();
+        ellipse(&mut pb, 0., body_height * 0.7, body_width * 0.75, body_height * 0.6, detail);
+        pb.close();
+
+        pb.finish()
+    });
+
+    let (leg_width, leg_height) = (rng.gen_range(6.0..8.0), rng.gen_range(23.0..28.0) * features.age.legs());
+
     let leg = {
         let mut pb = PathBuilder::new();
 
-        ellipse(&mut pb, 0., 0., rng.gen_range(6.0..8.0), rng.gen_range(23.0..28.0));
+        ellipse(&mut pb, 0., 0., leg_width, leg_height, detail);
 
         pb.finish()
     };
 
-    dt.set_transform(&base);
-    
-    dt.stroke(&tail, &BLACK, &StrokeStyle {
+    // Paws sit at the far end of each leg
+    let paw = {
+        let mut pb = PathBuilder::new();
+
+        ellipse(&mut pb, 0., leg_height - 4., leg_width * 0.75, 3.5, detail);
+        pb.close();
+
+        pb.finish()
+    };
+
+    let toes = features.toes.then(|| {
+        let mut pb = PathBuilder::new();
+
+        for x in [-leg_width / 3., leg_width / 3.] {
+            pb.move_to(x, leg_height);
+            pb.line_to(x, leg_height - 3.);
+        }
+
+        pb.finish()
+    });
+
+    // Tails held up are swung around where they meet the body
+    let (x, y) = pose.tail();
+    dt.set_transform(&match features.tail_up {
+        true => Transform::translation(-x, -y)
+            .then_rotate(Angle::degrees(-70.))
+            .then_translate(Vector::new(x, y))
+            .then(base),
+        false => *base,
+    });
+
+    dt.stroke(&tail, &colors.outline(), &StrokeStyle {
         cap: LineCap::Round,
         join: LineJoin::Miter,
         width: 7.,
         miter_limit: 2.,
         dash_array: Vec::new(),
         dash_offset: 0.,
-    }, &DRAW);
+    }, draw);
 
-    dt.set_transform(&Transform::rotation(Angle::degrees(-30.)).then_translate(Vector::new(-45., -19.)).then(&base));
-    dt.stroke(&neck, &BLACK, &stroke(), &DRAW);
-    dt.fill(&neck, &random_color(), &DRAW);
+    dt.set_transform(&pose.neck().then(base));
+    dt.stroke(&neck, &colors.outline(), stroke(), draw);
+    dt.fill(&neck, &colors.fill(rng), draw);
 
-    let legs = [
-        ((-45., 21.), 20.),
-        ((-25., 26.), 5.),
-        (( 25., 26.), -5.),
-        (( 45., 21.), -20.),
-    ];
+    for leg_position in pose.legs(features.legs) {
+        // Missing legs still get a color, so the rest of the cat stays the same
+        let fill = colors.fill(rng);
+        let Some(((x, y), rot)) = leg_position else {
+            continue;
+        };
 
-    for ((x, y), rot) in legs {
         let translation = Transform::rotation(Angle::degrees(rot)).then_translate(Vector::new(x, y));
 
-        dt.set_transform(&translation.then(&base));
-        dt.stroke(&leg, &BLACK, &stroke(), &DRAW);
-        dt.fill(&leg, &random_color(), &DRAW);
+        dt.set_transform(&translation.then(base));
+        dt.stroke(&leg, &colors.outline(), stroke(), draw);
+        dt.fill(&leg, &fill, draw);
+
+        dt.fill(&paw, &SHADE, draw);
+        if let Some(toes) = &toes {
+            dt.stroke(toes, &colors.outline(), &StrokeStyle {
+                cap: LineCap::Round,
+                width: 1.5,
+                ..StrokeStyle::default()
+            }, draw);
+        }
     }
 
-    dt.set_transform(&base);
+    dt.set_transform(&pose.body().then(base));
     
-    dt.stroke(&body, &BLACK, &stroke(), &DRAW);
-    dt.fill(&body, &random_color(), &DRAW);
+    dt.stroke(&body, &colors.outline(), stroke(), draw);
+    let body_color = colors.fill_color(rng);
+    dt.fill(&body, &Source::Solid(body_color), draw);
+
+    if let Some(belly) = &belly {
+        dt.push_clip(&body);
+        dt.fill(belly, &Source::Solid(lighten(body_color, 0.5)), draw);
+        dt.pop_clip();
+    }
 
-    // Draw head at (-59, 44).
-    dt.set_transform(&Transform::translation(-59., -44.).then(&base));
-    draw_head(dt);
-    dt.set_transform(&base);
+    let head = features.age.head();
+    let tilt = if features.tilted { -20. } else { 0. };
+    let (x, y) = pose.head();
+    dt.set_transform(&Transform::scale(head, head)
+        .then_rotate(Angle::degrees(tilt))
+        .then_translate(Vector::new(x, y))
+        .then(base));
+    draw_head(dt, rng, features, colors, draw, tolerance);
+    dt.set_transform(base);
 
 }
 
@@ -268,14 +2007,38 @@ fn stroke() -> &'static StrokeStyle {
     })
 }
 
-/// The default stroke options for shapes.
-const BLACK: Source = Source::Solid(SolidSource {
+/// A translucent black for shading darker details like paw pads.
+const SHADE: Source = Source::Solid(SolidSource {
     r: 0x0,
     g: 0x0,
     b: 0x0,
-    a: 0xff,
+    a: 0x40,
 });
 
+/// How the edges of shapes are smoothed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Antialias {
+    /// Hard, jagged edges, for pixel art cats.
+    None,
+    /// Smooth edges.
+    #[default]
+    Gray,
+}
+
+impl Antialias {
+    /// The draw options for shapes with this antialiasing.
+    pub fn draw_options(self) -> DrawOptions {
+        DrawOptions {
+            antialias: match self {
+                Antialias::None => AntialiasMode::None,
+                Antialias::Gray => AntialiasMode::Gray,
+            },
+            ..DRAW
+        }
+    }
+}
+
 /// The default draw options for shapes.
 const DRAW: DrawOptions = DrawOptions {
     blend_mode: BlendMode::SrcOver,
@@ -283,41 +2046,278 @@ const DRAW: DrawOptions = DrawOptions {
     antialias: AntialiasMode::Gray,
 };
 
-/// Generates a random (light) color.
-fn random_color<'a>() -> Source<'a> {
-    let mut rng = rand::thread_rng();
-    Source::Solid(SolidSource {
-        r: rng.gen_range(100..=255),
-        g: rng.gen_range(100..=255),
-        b: rng.gen_range(100..=255),
-        a: 0xff,
-    })
+/// The colors cats are drawn with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorScheme {
+    /// The color of outlines and details like the eyes, nose, and whiskers.
+    pub outline: SolidSource,
+
+    /// The lowest value for each channel of random fill colors.
+    pub min: u8,
+
+    /// The highest value for each channel of random fill colors.
+    pub max: u8,
+
+    /// A color that every fill color is pulled towards, along with how
+    /// strongly (from 0 to 1).
+    pub tint: Option<(SolidSource, f32)>,
+}
+
+impl ColorScheme {
+    /// The configured colors. The outline is black, unless the fill colors are
+    /// dark, in which case it's white so it stays visible.
+    pub fn configured(config: &Config) -> Self {
+        let value = if config.dark() { 0xff } else { 0x0 };
+        let outline = config.outline.map_or(SolidSource { r: value, g: value, b: value, a: 0xff }, SolidSource::from);
+
+        Self {
+            outline,
+            min: config.color_min,
+            max: config.color_max,
+            tint: None,
+        }
+    }
+
+    /// The colors for golden cats, which are shades of gold with brown
+    /// outlines.
+    pub fn golden() -> Self {
+        Self {
+            outline: SolidSource { r: 0x5a, g: 0x3a, b: 0x0, a: 0xff },
+            min: 0x80,
+            max: 0xff,
+            tint: Some((SolidSource { r: 0xff, g: 0xc4, b: 0x20, a: 0xff }, 0.75)),
+        }
+    }
+
+    /// The color for outlines, text, and other details.
+    pub fn outline<'a>(&self) -> Source<'a> {
+        Source::Solid(self.outline)
+    }
+
+    /// Generates a random fill color in the brightness range.
+    pub fn fill<'a>(&self, rng: &mut impl Rng) -> Source<'a> {
+        Source::Solid(self.fill_color(rng))
+    }
+
+    /// Like [ColorScheme::fill], but returns the color itself.
+    pub fn fill_color(&self, rng: &mut impl Rng) -> SolidSource {
+        let range = self.min.min(self.max)..=self.max;
+
+        let (r, g, b) = (rng.gen_range(range.clone()), rng.gen_range(range.clone()), rng.gen_range(range));
+
+        let (r, g, b) = match self.tint {
+            Some((tint, strength)) => {
+                let mix = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * strength.clamp(0., 1.)) as u8;
+                (mix(r, tint.r), mix(g, tint.g), mix(b, tint.b))
+            }
+            None => (r, g, b),
+        };
+
+        SolidSource { r, g, b, a: 0xff }
+    }
+}
+
+/// An opaque color, written like `ff8800` (optionally with a `#`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl FromStr for Rgb {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.trim().trim_start_matches('#');
+        let color = u32::from_str_radix(hex, 16).ok()
+            .filter(|_| hex.len() == 6)
+            .ok_or_else(|| format!("'{s}' isn't a color like ff8800"))?;
+
+        Ok(Self { r: (color >> 16) as u8, g: (color >> 8) as u8, b: color as u8 })
+    }
+}
+
+impl TryFrom<String> for Rgb {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Rgb> for SolidSource {
+    fn from(Rgb { r, g, b }: Rgb) -> Self {
+        SolidSource { r, g, b, a: 0xff }
+    }
+}
+
+/// Pulls a color towards white by `amount` (from 0 to 1).
+fn lighten(color: SolidSource, amount: f32) -> SolidSource {
+    let lighten = |channel: u8| channel + ((0xff - channel) as f32 * amount.clamp(0., 1.)) as u8;
+
+    SolidSource { r: lighten(color.r), g: lighten(color.g), b: lighten(color.b), a: color.a }
 }
 
-/// Draws an ellipse on the given path.
+/// Draws an ellipse on the given path, centered on `x, y` with the given
+/// radii, in the path's coordinates. `detail` says how big the path ends up,
+/// so big ellipses get split into enough curves that they stay within the
+/// tolerance of a real ellipse.
 /// This is a generalization of the function called on [PathBuilder::arc], and
 /// will ideally be unnecessary when [the PR](https://github.com/jrmuizel/raqote/pull/207/)
 /// is dealt with.
-fn ellipse(pb: &mut PathBuilder, x: f32, y: f32, width: f32, height: f32) {
-    let a: Arc<f32> = Arc {
-        center: Point::new(x, y),
-        radii: Vector::new(width, height),
-        start_angle: Angle::radians(0.),
-        sweep_angle: Angle::radians(std::f32::consts::PI * 2.),
-        x_rotation: Angle::zero(),
-    };
-    let start = a.from();
+pub fn ellipse(pb: &mut PathBuilder, x: f32, y: f32, width: f32, height: f32, detail: Detail) {
+    let segments = ellipse_segments(width.abs().max(height.abs()) * detail.scale, detail.tolerance);
+    let sweep = std::f32::consts::TAU / segments as f32;
+
+    let start = Point::new(x + width, y);
     pb.move_to(start.x, start.y);
-    a.for_each_quadratic_bezier(&mut |q| {
-        pb.quad_to(q.ctrl.x, q.ctrl.y, q.to.x, q.to.y);
-    });
+
+    for segment in 0..segments {
+        let a: Arc<f32> = Arc {
+            center: Point::new(x, y),
+            radii: Vector::new(width, height),
+            start_angle: Angle::radians(sweep * segment as f32),
+            sweep_angle: Angle::radians(sweep),
+            x_rotation: Angle::zero(),
+        };
+        a.for_each_quadratic_bezier(&mut |q| {
+            pb.quad_to(q.ctrl.x, q.ctrl.y, q.to.x, q.to.y);
+        });
+    }
+}
+
+/// Draws a rectangle with circular corners of the given radius on the given
+/// path, with its top left corner at `x, y`.
+fn rounded_rect(pb: &mut PathBuilder, x: f32, y: f32, width: f32, height: f32, radius: f32) {
+    // How far along the corner the control points go for a circular arc
+    const KAPPA: f32 = 0.552_284_8;
+
+    let r = radius.min(width / 2.).min(height / 2.);
+    let k = r * (1. - KAPPA);
+    let (right, bottom) = (x + width, y + height);
+
+    pb.move_to(x + r, y);
+    pb.line_to(right - r, y);
+    pb.cubic_to(right - k, y, right, y + k, right, y + r);
+    pb.line_to(right, bottom - r);
+    pb.cubic_to(right, bottom - k, right - k, bottom, right - r, bottom);
+    pb.line_to(x + r, bottom);
+    pb.cubic_to(x + k, bottom, x, bottom - k, x, bottom - r);
+    pb.line_to(x, y + r);
+    pb.cubic_to(x, y + k, x + k, y, x + r, y);
+    pb.close();
+}
+
+/// Returns how many curves an ellipse with the given radius (in pixels) is
+/// split into. A quadratic curve sweeping `θ` of a circle is off by about
+/// `r * θ⁴ / 128`, so this picks the biggest sweep that's within the
+/// tolerance (also in pixels, see [Config::curve_tolerance]), and never fewer
+/// than the 8 curves ellipses have always had.
+pub fn ellipse_segments(radius: f32, tolerance: f32) -> usize {
+    const MAX_SEGMENTS: usize = 256;
+
+    let sweep = (tolerance * 128. / radius).powf(0.25).min(std::f32::consts::FRAC_PI_4);
+
+    ((std::f32::consts::TAU / sweep).ceil() as usize).clamp(8, MAX_SEGMENTS)
+}
+
+/// How finely curves are drawn (see [ellipse]).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Detail {
+    /// How many pixels one unit of the path ends up as.
+    pub scale: f32,
+    /// How far curves can stray from the real shape, in pixels.
+    pub tolerance: f32,
+}
+
+/// Returns the detail for a path drawn with the given transform.
+fn detail(transform: &Transform, tolerance: f32) -> Detail {
+    Detail { scale: transform.m11.hypot(transform.m12), tolerance }
+}
+
+/// Adds the shape through `points` on the given path, along with its mirror
+/// image across the y axis, so both sides are guaranteed to be symmetric.
+/// The shape is closed if `close` is true.
+fn mirrored(pb: &mut PathBuilder, points: &[(f32, f32)], close: bool) {
+    for side in [1., -1.] {
+        let Some(((x, y), rest)) = points.split_first() else {
+            return;
+        };
+
+        pb.move_to(side * x, *y);
+        for (x, y) in rest {
+            pb.line_to(side * x, *y);
+        }
+
+        if close {
+            pb.close();
+        }
+    }
+}
+
+/// The file format of a generated image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    /// Lossless, transparent, and the default.
+    Png,
+    /// Smaller than PNG, but cats are flat colors with sharp outlines, which
+    /// JPEG is bad at, so there's some ringing around the edges, especially at
+    /// lower qualities. JPEGs can't be transparent, so cats are drawn on white
+    /// (or black, if the colors are dark).
+    #[serde(alias = "jpg")]
+    Jpeg,
+    /// The raw, unpremultiplied RGBA pixels, row by row, without any header.
+    /// This is for tools that want to do their own encoding (see
+    /// [purchase_cat_rgba]), so it can't be asked for in a query.
+    #[serde(skip)]
+    Rgba,
+}
+
+impl Format {
+    /// The MIME type of the format.
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Png => "image/png",
+            Format::Jpeg => "image/jpeg",
+            Format::Rgba => "application/octet-stream",
+        }
+    }
+
+    /// The format with the given MIME type, if there is one.
+    pub fn from_content_type(kind: &str) -> Option<Format> {
+        [Format::Png, Format::Jpeg, Format::Rgba].into_iter()
+            .find(|format| format.content_type() == kind)
+    }
+
+    /// The file extension for the format, without the dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Png => "png",
+            Format::Jpeg => "jpg",
+            Format::Rgba => "rgba",
+        }
+    }
+}
+
+/// Information about how a cat was made, which is embedded in its PNG so it can
+/// be reproduced later.
+///
+/// There's deliberately no timestamp, so the same seed always gives the same
+/// bytes.
+pub struct Metadata {
+    /// The seed the cat was drawn from.
+    pub seed: u64,
 }
 
-/// Renders a canvas to a PNG.
+/// Renders a canvas to a PNG, writing the metadata (if there is any) into
+/// `tEXt` chunks alongside the version of makea.cat.
 /// 
 /// This is an adaptation of the code in raqote:
 /// https://github.com/jrmuizel/raqote/blob/master/src/draw_target.rs#L1096
-fn canvas_to_png(canvas: DrawTarget) -> Result<Vec<u8>> {
+fn canvas_to_png(canvas: &DrawTarget, metadata: Option<&Metadata>) -> Result<Vec<u8>> {
 
     let mut file = Vec::new();
 
@@ -327,30 +2327,392 @@ fn canvas_to_png(canvas: DrawTarget) -> Result<Vec<u8>> {
         let mut encoder = png::Encoder::new(w, canvas.width() as u32, canvas.height() as u32);
         encoder.set_color(png::ColorType::Rgba);
         encoder.set_depth(png::BitDepth::Eight);
+
+        if let Some(metadata) = metadata {
+            encoder.add_text_chunk("Software".into(), format!("makea.cat {}", env!("CARGO_PKG_VERSION")))?;
+            encoder.add_text_chunk("Seed".into(), metadata.seed.to_string())?;
+        }
+
         let mut writer = encoder.write_header()?;
-        let buf = canvas.get_data();
-        let mut output = Vec::with_capacity(buf.len() * 4);
-
-        for pixel in buf {
-            let a = (pixel >> 24) & 0xffu32;
-            let mut r = (pixel >> 16) & 0xffu32;
-            let mut g = (pixel >> 8) & 0xffu32;
-            let mut b = (pixel >> 0) & 0xffu32;
-
-            if a > 0u32 {
-                r = r * 255u32 / a;
-                g = g * 255u32 / a;
-                b = b * 255u32 / a;
+        writer.write_image_data(&canvas_to_rgba(canvas))?;
+    }
+
+    Ok(file)
+}
+
+/// Turns a canvas's premultiplied pixels into plain RGBA bytes, row by row.
+fn canvas_to_rgba(canvas: &DrawTarget) -> Vec<u8> {
+    let buf = canvas.get_data();
+    let mut output = Vec::with_capacity(buf.len() * 4);
+
+    for pixel in buf {
+        let a = (pixel >> 24) & 0xffu32;
+        let r = (pixel >> 16) & 0xffu32;
+        let g = (pixel >> 8) & 0xffu32;
+        let b = pixel & 0xffu32;
+
+        // Unpremultiply, leaving fully transparent pixels alone
+        let r = (r * 255u32).checked_div(a).unwrap_or(r);
+        let g = (g * 255u32).checked_div(a).unwrap_or(g);
+        let b = (b * 255u32).checked_div(a).unwrap_or(b);
+
+        output.push(r as u8);
+        output.push(g as u8);
+        output.push(b as u8);
+        output.push(a as u8);
+    }
+
+    output
+}
+
+/// A small PNG of a red X, for when an image couldn't be made, so browsers
+/// show something instead of a broken image.
+///
+/// This doesn't use raqote (or fonts, or anything else that might have been
+/// what failed), and is only encoded once. It's empty in the very unlikely
+/// case that even this can't be encoded.
+pub fn error_image() -> Vec<u8> {
+    static ERROR: OnceLock<Vec<u8>> = OnceLock::new();
+    ERROR.get_or_init(|| {
+        const SIZE: u32 = 64;
+        const MARGIN: u32 = 12;
+
+        let mut pixels = Vec::with_capacity((SIZE * SIZE * 4) as usize);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                // Near either diagonal, but not too close to the edges
+                let inside = (MARGIN..SIZE - MARGIN).contains(&x) && (MARGIN..SIZE - MARGIN).contains(&y);
+                let cross = x.abs_diff(y) < 5 || (x + y).abs_diff(SIZE - 1) < 5;
+
+                pixels.extend(if inside && cross { [0xd0, 0x20, 0x20, 0xff] } else { [0, 0, 0, 0] });
             }
+        }
 
-            output.push(r as u8);
-            output.push(g as u8);
-            output.push(b as u8);
-            output.push(a as u8);
+        let mut file = Vec::new();
+        let result = (|| {
+            let mut encoder = png::Encoder::new(&mut file, SIZE, SIZE);
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder.write_header()?.write_image_data(&pixels)
+        })();
+
+        match result {
+            Ok(()) => file,
+            Err(err) => {
+                error!("Couldn't even encode the error image: {err}");
+                Vec::new()
+            }
         }
+    }).clone()
+}
+
+/// Renders a canvas to a JPEG with the given quality (from 1 to 100).
+fn canvas_to_jpeg(canvas: &DrawTarget, quality: u8, dark: bool) -> Result<Vec<u8>> {
+    let buf = canvas.get_data();
+    let mut output = Vec::with_capacity(buf.len() * 3);
+
+    // There's no transparency, so flatten onto a background that the outlines
+    // still show up on
+    let background = if dark { 0x0 } else { 0xffu32 };
 
-        writer.write_image_data(&output)?;
+    for pixel in buf {
+        let a = (pixel >> 24) & 0xffu32;
+
+        // The colors are premultiplied, so they're already on black
+        for shift in [16, 8, 0] {
+            let color = (pixel >> shift) & 0xffu32;
+            output.push((color + background * (255 - a) / 255) as u8);
+        }
     }
 
+    let mut file = Vec::new();
+    JpegEncoder::new_with_quality(&mut file, quality.clamp(1, 100))
+        .encode(&output, canvas.width() as u32, canvas.height() as u32, ExtendedColorType::Rgb8)?;
+
     Ok(file)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legs_from_numbers_and_strings() {
+        assert_eq!(serde_json::from_str::<Legs>("3").unwrap(), Legs::Three);
+        assert_eq!(serde_json::from_str::<Legs>(r#""3""#).unwrap(), Legs::Three);
+        assert_eq!(serde_json::from_str::<Legs>("4").unwrap(), Legs::Four);
+        assert!(serde_json::from_str::<Legs>("5").is_err());
+        assert!(serde_json::from_str::<Legs>(r#""three""#).is_err());
+
+        let options: CatOptions = serde_json::from_str(r#"{"legs": 3}"#).unwrap();
+        assert_eq!(options.legs, Some(Legs::Three));
+        let options: CatOptions = serde_json::from_str(r#"{"legs": "3"}"#).unwrap();
+        assert_eq!(options.legs, Some(Legs::Three));
+
+        let uri = "/cat?legs=3".parse().unwrap();
+        let axum::extract::Query(options) = axum::extract::Query::<CatOptions>::try_from_uri(&uri).unwrap();
+        assert_eq!(options.legs, Some(Legs::Three));
+    }
+
+    /// Draws a cat as raw pixels, without anything on the canvas but the cat.
+    fn pixels(options: CatOptions, seed: u64) -> Cat {
+        let options = CatOptions { format: Format::Rgba, mark: false, ..options };
+        purchase_cat(&options, seed, &Config::default()).unwrap()
+    }
+
+    /// Returns whether every pixel that isn't transparent is inside the cat's
+    /// bounds.
+    fn inside_bounds(cat: &Cat, width: u32) -> bool {
+        let Some(Bounds { x, y, width: w, height: h }) = cat.bounds else {
+            return cat.image.chunks(4).all(|pixel| pixel[3] == 0);
+        };
+
+        cat.image.chunks(4).enumerate()
+            .filter(|(_, pixel)| pixel[3] != 0)
+            .all(|(i, _)| {
+                let (px, py) = (i as u32 % width, i as u32 / width);
+                (x..x + w).contains(&px) && (y..y + h).contains(&py)
+            })
+    }
+
+    #[test]
+    fn bounds_cover_the_cats() {
+        let config = Config::default();
+
+        for seed in 0..20 {
+            for options in [
+                CatOptions::default(),
+                CatOptions { shadow: true, ..CatOptions::default() },
+                CatOptions { count: 3, ..CatOptions::default() },
+                CatOptions { fit: true, scale: 2, ..CatOptions::default() },
+            ] {
+                let cat = pixels(options.clone(), seed);
+                let bounds = cat.bounds.unwrap();
+                assert!(inside_bounds(&cat, config.width as u32 * options.scale), "seed {seed} with {options:?}");
+
+                // The box is around the cat, not the whole canvas
+                assert!(bounds.width < config.width as u32 * options.scale || bounds.height < config.height as u32 * options.scale);
+            }
+        }
+
+        // The rainbow trail isn't part of the cat
+        for seed in 0..20 {
+            let plain = pixels(CatOptions::default(), seed);
+            let rainbow = pixels(CatOptions { style: Some(Style::Rainbow), ..CatOptions::default() }, seed);
+            assert_eq!(plain.bounds, rainbow.bounds);
+        }
+
+        // ...and neither is the scene, even where the cat is the same color
+        let scene = pixels(CatOptions { scene: Some(Scene::Window), ..CatOptions::default() }, 1);
+        assert_eq!(scene.bounds, pixels(CatOptions::default(), 1).bounds);
+    }
+
+    /// Returns whether any pixel along the edge of the image isn't
+    /// transparent.
+    fn touches_edge(cat: &Cat, width: u32, height: u32) -> bool {
+        cat.image.chunks(4).enumerate()
+            .filter(|(_, pixel)| pixel[3] != 0)
+            .any(|(i, _)| {
+                let (x, y) = (i as u32 % width, i as u32 / width);
+                x == 0 || y == 0 || x == width - 1 || y == height - 1
+            })
+    }
+
+    #[test]
+    fn cats_on_tall_and_wide_canvases() {
+        for (width, height) in [(200, 600), (600, 200)] {
+            let config = Config { width, height, ..Config::default() };
+            let (width, height) = (width as u32, height as u32);
+
+            for seed in 0..20 {
+                let options = CatOptions { format: Format::Rgba, mark: false, fit: true, ..CatOptions::default() };
+                let cat = purchase_cat(&options, seed, &config).unwrap();
+                assert_eq!(cat.image.len() as u32, width * height * 4);
+
+                // Fitted cats stay on the canvas (apart from very long tails),
+                // and are sized by the smaller side
+                if cat.features[0].tail != Tail::Long {
+                    assert!(!touches_edge(&cat, width, height), "seed {seed} on {width}x{height}");
+                }
+
+                let bounds = cat.bounds.unwrap();
+                assert!(bounds.width.max(bounds.height) > width.min(height) / 2, "seed {seed} on {width}x{height}");
+            }
+        }
+
+        // Cats are the same size on any canvas with the same smaller side, so
+        // they're only moved around (unless they're cut off by the edge). They
+        // can land on different parts of a pixel, so the size can be off by one.
+        let size = |width, height, seed| {
+            let config = Config { width, height, ..Config::default() };
+            let bounds = purchase_cat(&CatOptions::default(), seed, &config).unwrap().bounds.unwrap();
+            let cut = bounds.x == 0 || bounds.y == 0 || bounds.x + bounds.width == width as u32 || bounds.y + bounds.height == height as u32;
+            (!cut).then_some((bounds.width, bounds.height))
+        };
+
+        let mut compared = 0;
+        for seed in 0..20 {
+            if let (Some(tall), Some(wide), Some(square)) = (size(200, 600, seed), size(600, 200, seed), size(200, 200, seed)) {
+                let close = |(a, b): (u32, u32), (c, d): (u32, u32)| a.abs_diff(c) <= 1 && b.abs_diff(d) <= 1;
+                assert!(close(tall, wide) && close(tall, square), "seed {seed}: {tall:?}, {wide:?}, {square:?}");
+                compared += 1;
+            }
+        }
+        assert!(compared > 0);
+    }
+
+    #[test]
+    fn reused_canvases_start_blank() {
+        let config = Config::default();
+        let (width, height) = (config.width, config.height);
+
+        with_canvas(&config, width, height, |dt| {
+            dt.set_transform(&Transform::scale(2., 2.));
+            dt.clear(SolidSource { r: 0xff, g: 0, b: 0, a: 0xff });
+        });
+
+        with_canvas(&config, width, height, |dt| {
+            assert!(dt.get_data().iter().all(|&pixel| pixel == 0));
+            assert_eq!(*dt.get_transform(), Transform::identity());
+        });
+
+        // A cat drawn after a busier one is the same as one drawn on a fresh
+        // thread, which has never drawn anything
+        let options = CatOptions { format: Format::Rgba, ..CatOptions::default() };
+        let fresh = std::thread::spawn({
+            let options = options.clone();
+            move || purchase_cat(&options, 7, &Config::default()).unwrap().image
+        }).join().unwrap();
+
+        let busy = CatOptions { scene: Some(Scene::Window), count: 3, shadow: true, border: 6, ..options.clone() };
+        purchase_cat(&busy, 8, &config).unwrap();
+        assert_eq!(purchase_cat(&options, 7, &config).unwrap().image, fresh);
+    }
+
+    #[test]
+    fn names_pick_the_same_cat() {
+        assert_eq!(name_seed("alice"), name_seed("alice"));
+        assert_ne!(name_seed("alice"), name_seed("bob"));
+        assert_ne!(name_seed("alice"), name_seed("Alice"));
+        assert_ne!(name_seed(""), name_seed("alice"));
+
+        // 64-bit FNV-1a, which mustn't change or everyone's cat would too
+        assert_eq!(name_seed(""), 0xcbf29ce484222325);
+        assert_eq!(name_seed("a"), 0xaf63dc4c8601ec8c);
+
+        let config = Config::default();
+        let options = CatOptions { format: Format::Rgba, ..CatOptions::default() };
+        let cat = |name| purchase_cat(&options, name_seed(name), &config).unwrap();
+
+        assert_eq!(cat("alice").image, cat("alice").image);
+        assert_eq!(cat("alice").features, cat("alice").features);
+        assert_ne!(cat("alice").image, cat("bob").image);
+    }
+
+    #[test]
+    fn daily_themes_follow_the_date() {
+        let config = Config::default();
+        let options = CatOptions { themed: Some(Theme::Daily), format: Format::Rgba, ..CatOptions::default() };
+        let cat = |date: &str| purchase_cat(&CatOptions { date: Some(date.parse().unwrap()), ..options.clone() }, 7, &config).unwrap().image;
+
+        assert_eq!(cat("2024-01-01"), cat("2024-01-01"));
+        assert_ne!(cat("2024-01-01"), cat("2024-01-02"));
+
+        // Without a date, there's nothing to pick the tint
+        let plain = CatOptions { format: Format::Rgba, ..CatOptions::default() };
+        assert_eq!(purchase_cat(&options, 7, &config).unwrap().image, purchase_cat(&plain, 7, &config).unwrap().image);
+    }
+
+    #[test]
+    fn bigger_ellipses_get_more_segments() {
+        let tolerance = Config::default().curve_tolerance;
+        let quads = |scale: f32| {
+            let mut pb = PathBuilder::new();
+            ellipse(&mut pb, 0., 0., 25., 24., Detail { scale, tolerance });
+            pb.finish().ops.iter().filter(|op| matches!(op, PathOp::QuadTo(..))).count()
+        };
+
+        // Small ellipses still have the 8 curves they always had
+        assert_eq!(ellipse_segments(25., tolerance), 8);
+        assert_eq!(quads(1.), 8);
+
+        // ...but ellipses drawn 16 times bigger need more to stay smooth
+        assert!(ellipse_segments(25. * 16., tolerance) > 8);
+        assert_eq!(quads(16.), ellipse_segments(25. * 16., tolerance));
+
+        // Never more than the cap, however big or fine
+        assert_eq!(ellipse_segments(1e9, tolerance), 256);
+        assert_eq!(ellipse_segments(25., 1e-9), 256);
+
+        // Each segment is off by about r * θ⁴ / 128, which is within the
+        // tolerance
+        for radius in [100., 400., 1600.] {
+            let sweep = std::f32::consts::TAU / ellipse_segments(radius, tolerance) as f32;
+            assert!(radius * sweep.powi(4) / 128. <= tolerance * 1.001, "radius {radius}");
+        }
+    }
+
+    #[test]
+    fn fitted_cats_on_their_side() {
+        let config = Config::default();
+        let (width, height) = (config.width as f32, config.height as f32);
+        let size = width.min(height) / 256.;
+
+        for rotation in [90., -90., 45., 0.] {
+            let turned: Transform = Transform2D::identity()
+                .then_scale(size * 1.1, size * 1.1)
+                .then_rotate(Angle::degrees(rotation));
+            let (turned, (x, wiggle_x), (y, wiggle_y)) = fit(turned, width, height);
+
+            for (dx, dy) in [(-1., -1.), (-1., 1.), (1., -1.), (1., 1.)] {
+                let base = turned.then_translate(Vector::new(x + dx * wiggle_x, y + dy * wiggle_y));
+
+                // The rough corners of the cat are on the canvas wherever it
+                // moves to
+                let [(left, top), (right, bottom)] = CAT_BOUNDS;
+                for (cx, cy) in [(left, top), (right, top), (left, bottom), (right, bottom)] {
+                    let corner = base.transform_point(Point::new(cx, cy));
+                    assert!((-0.5..=width + 0.5).contains(&corner.x) && (-0.5..=height + 0.5).contains(&corner.y), "{rotation}° at {corner:?}");
+                }
+
+                // ...and almost all of the real cat is too
+                for seed in 0..10 {
+                    let options = CatOptions { fit: true, ..CatOptions::default() };
+                    let features = roll_features(&options, seed, &config).unwrap().remove(0);
+                    if features.tail == Tail::Long {
+                        continue;
+                    }
+
+                    let mut dt = DrawTarget::new(config.width, config.height);
+                    let mut cat = Extents { surface: &mut dt, covered: None };
+                    draw_cat(&mut cat, &mut ChaCha8Rng::seed_from_u64(seed), &base, &features, &ColorScheme::configured(&config), &DRAW, config.curve_tolerance);
+
+                    let covered = cat.covered.unwrap();
+                    let inside = covered.intersection(&Box2D::new(Point::zero(), Point::new(width, height)));
+                    assert!(inside.map_or(0., |inside| inside.area()) / covered.area() > 0.9, "{rotation}° with seed {seed}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn builder_sizes_and_svg() {
+        let cat = CatBuilder::new().seed(3).width(300).height(200).format(Format::Rgba, 90).render().unwrap();
+        assert_eq!(cat.image.len(), 300 * 200 * 4);
+
+        let builder = CatBuilder::new().seed(3).shadow(true).width(300).height(200);
+        let svg = builder.render_svg().unwrap();
+        assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="300" height="200" viewBox="0 0 300 200">"#));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<g ").count(), svg.matches("</g>").count());
+        assert_eq!(svg, builder.render_svg().unwrap());
+        assert_ne!(svg, builder.clone().seed(4).render_svg().unwrap());
+
+        // The shadow is faded all at once, and the outline is black
+        assert!(svg.contains(r#"<g opacity="0.25">"#));
+        assert!(svg.contains(r##"stroke="#000000""##));
+
+        assert!(matches!(CatBuilder::new().width(0).render_svg(), Err(CanvasError::Empty)));
+        assert!(matches!(CatBuilder::new().height(-5).render(), Err(CanvasError::Empty)));
+    }
+}
@@ -0,0 +1,15 @@
+pub mod access;
+pub mod admin;
+pub mod config;
+pub mod connection;
+pub mod live;
+pub mod logging;
+pub mod metrics;
+pub mod nonce;
+pub mod range;
+pub mod server;
+pub mod svg;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod time;
+pub mod draw;
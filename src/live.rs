@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+use axum::{extract::{ws::{Message, WebSocket}, State, WebSocketUpgrade}, response::Response};
+use chrono::Utc;
+use log::info;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::{config::Config, time::available_anywhere};
+
+/// How often the availability is checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Announces whether cats can be made anywhere every time it changes.
+#[derive(Clone)]
+pub struct Availability {
+    sender: broadcast::Sender<bool>,
+    config: &'static Config,
+}
+
+impl Availability {
+    /// Starts checking the availability (per the given config) in the
+    /// background.
+    pub fn watch(config: &'static Config) -> Self {
+        let (sender, _) = broadcast::channel(16);
+
+        let announce = sender.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(POLL_INTERVAL);
+            let mut available = available_anywhere(config, Utc::now());
+
+            loop {
+                interval.tick().await;
+
+                let now = available_anywhere(config, Utc::now());
+                if now != available {
+                    available = now;
+                    info!("Cats are now {}", if available { "available" } else { "unavailable" });
+
+                    // Nobody might be listening, which is fine
+                    let _ = announce.send(available);
+                }
+            }
+        });
+
+        Self { sender, config }
+    }
+
+    /// Listens for every time the availability changes.
+    pub fn subscribe(&self) -> broadcast::Receiver<bool> {
+        self.sender.subscribe()
+    }
+
+    /// Whether cats can be made anywhere right now.
+    pub fn now(&self) -> bool {
+        available_anywhere(self.config, Utc::now())
+    }
+}
+
+/// The `/ws` endpoint, which sends `{"available":true}` or
+/// `{"available":false}` as soon as a client connects and again every time
+/// that changes.
+pub async fn socket(ws: WebSocketUpgrade, State(availability): State<Availability>) -> Response {
+    ws.on_upgrade(move |socket| announce(socket, availability))
+}
+
+/// Sends the availability to a single client until it goes away.
+async fn announce(mut socket: WebSocket, availability: Availability) {
+    let mut changes = availability.subscribe();
+
+    if send(&mut socket, availability.now()).await.is_err() {
+        return;
+    }
+
+    loop {
+        tokio::select! {
+            change = changes.recv() => {
+                let available = match change {
+                    Ok(available) => available,
+                    // Missed some changes, so just send whatever it is now
+                    Err(RecvError::Lagged(_)) => availability.now(),
+                    Err(RecvError::Closed) => return,
+                };
+
+                if send(&mut socket, available).await.is_err() {
+                    return;
+                }
+            }
+            // Clients don't have anything to say, so only listen for them leaving
+            message = socket.recv() => {
+                if let Some(Ok(Message::Close(_)) | Err(_)) | None = message {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Sends the availability to a client.
+async fn send(socket: &mut WebSocket, available: bool) -> Result<(), axum::Error> {
+    socket.send(Message::Text(format!(r#"{{"available":{available}}}"#))).await
+}
@@ -0,0 +1,77 @@
+use std::{io::Write, str::FromStr};
+
+use chrono::{SecondsFormat, Utc};
+use env_logger::Builder;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// How log lines are written, set with `CAT_LOG_FORMAT`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(try_from = "String")]
+pub enum LogFormat {
+    /// env_logger's usual human-readable lines, for running locally.
+    #[default]
+    Pretty,
+
+    /// One JSON object per line, for feeding into log aggregators.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "pretty" => Ok(Self::Pretty),
+            "json" => Ok(Self::Json),
+            other => Err(format!("unknown log format {other}, expected json or pretty")),
+        }
+    }
+}
+
+impl TryFrom<String> for LogFormat {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+/// Sets up logging in the given format. Levels are still picked with
+/// `RUST_LOG`.
+pub fn init(format: LogFormat) {
+    let mut builder = Builder::from_default_env();
+
+    if format == LogFormat::Json {
+        builder.format(|buf, record| {
+            let mut line = Map::new();
+            line.insert("time".into(), Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true).into());
+            line.insert("level".into(), record.level().as_str().into());
+            line.insert("target".into(), record.target().into());
+
+            let message = record.args().to_string();
+
+            // Lines like the access log are already `key=value` pairs, so
+            // they're split into their own fields to be searchable
+            match fields(&message) {
+                Some(fields) => line.extend(fields),
+                None => { line.insert("message".into(), message.into()); }
+            }
+
+            writeln!(buf, "{}", Value::Object(line))
+        });
+    }
+
+    builder.init();
+}
+
+/// Splits a message made entirely of `key=value` pairs into fields, or returns
+/// `None` if it's just a regular message.
+fn fields(message: &str) -> Option<Vec<(String, Value)>> {
+    message.split_whitespace()
+        .map(|pair| pair.split_once('=')
+            .filter(|(key, _)| !key.is_empty())
+            .map(|(key, value)| (key.to_owned(), value.into())))
+        .collect::<Option<Vec<_>>>()
+        .filter(|fields| !fields.is_empty())
+}
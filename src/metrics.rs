@@ -0,0 +1,254 @@
+use std::{collections::BTreeMap, fmt::Write, sync::atomic::{AtomicU64, Ordering}, time::Duration};
+
+use serde::Serialize;
+
+use crate::time::RejectReason;
+
+/// Counters for what the server has been doing, exposed at `/metrics`.
+pub struct Metrics {
+    /// The number of cats made.
+    pub cats: AtomicU64,
+
+    /// The number of placeholder images requested on purpose (e.g. `/torna`).
+    pub placeholders: AtomicU64,
+
+    /// The number of images currently waiting for their turn to be drawn.
+    pub queued_renders: AtomicU64,
+
+    /// How long it took to draw each cat.
+    pub render_duration: Histogram,
+
+    /// How long each cat request took, from the handler starting to the image
+    /// being ready (including waiting for a turn to be drawn).
+    pub cat_latency: Histogram,
+
+    /// The number of cat requests rejected by the anticheat, indexed by
+    /// [RejectReason].
+    rejections: [AtomicU64; RejectReason::ALL.len()],
+}
+
+/// The upper bounds of each histogram bucket, in microseconds. Most cats take
+/// a millisecond or two to draw, so the buckets are bunched up around there.
+const BUCKETS: [u64; 6] = [500, 1_000, 2_000, 5_000, 10_000, 50_000];
+
+/// Counts how many durations fell into each of [BUCKETS], for Prometheus.
+pub struct Histogram {
+    /// How many durations were at most each bucket's bound (not cumulative).
+    buckets: [AtomicU64; BUCKETS.len()],
+    /// How many durations there were in total, including any past the last
+    /// bucket.
+    count: AtomicU64,
+    /// The total of every duration, in microseconds.
+    sum: AtomicU64,
+}
+
+impl Histogram {
+    /// Makes an empty histogram.
+    pub const fn new() -> Self {
+        Self {
+            buckets: [const { AtomicU64::new(0) }; BUCKETS.len()],
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+        }
+    }
+
+    /// Counts a single duration.
+    pub fn observe(&self, duration: Duration) {
+        let micros = duration.as_micros().try_into().unwrap_or(u64::MAX);
+
+        if let Some(bucket) = BUCKETS.iter().position(|&bound| micros <= bound) {
+            self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(micros, Ordering::Relaxed);
+    }
+
+    /// Returns how many durations were at most each bucket's bound (so each
+    /// count includes the ones before it), in microseconds, followed by the
+    /// total count.
+    pub fn cumulative(&self) -> ([(u64, u64); BUCKETS.len()], u64) {
+        let mut total = 0;
+        let buckets = std::array::from_fn(|i| {
+            total += self.buckets[i].load(Ordering::Relaxed);
+            (BUCKETS[i], total)
+        });
+
+        (buckets, self.count.load(Ordering::Relaxed))
+    }
+
+    /// Empties the histogram.
+    fn reset(&self) {
+        for bucket in &self.buckets {
+            bucket.store(0, Ordering::Relaxed);
+        }
+
+        self.count.store(0, Ordering::Relaxed);
+        self.sum.store(0, Ordering::Relaxed);
+    }
+
+    /// Renders the histogram in the Prometheus text format, in seconds.
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} histogram");
+
+        let (buckets, count) = self.cumulative();
+        for (bound, total) in buckets {
+            let _ = writeln!(out, "{name}_bucket{{le=\"{}\"}} {total}", bound as f64 / 1e6);
+        }
+
+        let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {count}");
+        let _ = writeln!(out, "{name}_sum {}", self.sum.load(Ordering::Relaxed) as f64 / 1e6);
+        let _ = writeln!(out, "{name}_count {count}");
+    }
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    /// Makes metrics where nothing has happened yet.
+    pub const fn new() -> Self {
+        Self {
+            cats: AtomicU64::new(0),
+            placeholders: AtomicU64::new(0),
+            queued_renders: AtomicU64::new(0),
+            render_duration: Histogram::new(),
+            cat_latency: Histogram::new(),
+            rejections: [const { AtomicU64::new(0) }; RejectReason::ALL.len()],
+        }
+    }
+
+    /// Counts a request rejected for the given reason.
+    pub fn reject(&self, reason: RejectReason) {
+        self.rejections[reason as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Reads every counter at once.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            cats: self.cats.load(Ordering::Relaxed),
+            placeholders: self.placeholders.load(Ordering::Relaxed),
+            queued_renders: self.queued_renders.load(Ordering::Relaxed),
+            rejections: RejectReason::ALL.into_iter()
+                .map(|reason| (reason.to_string(), self.rejections[reason as usize].load(Ordering::Relaxed)))
+                .collect(),
+        }
+    }
+
+    /// Sets every counter back to zero. Gauges (like the render queue) are
+    /// left alone, since they're still true.
+    pub fn reset(&self) {
+        self.cats.store(0, Ordering::Relaxed);
+        self.placeholders.store(0, Ordering::Relaxed);
+        self.render_duration.reset();
+        self.cat_latency.reset();
+
+        for rejections in &self.rejections {
+            rejections.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Renders the metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        let _ = writeln!(out, "# HELP makea_cat_cats_total Cats made.");
+        let _ = writeln!(out, "# TYPE makea_cat_cats_total counter");
+        let _ = writeln!(out, "makea_cat_cats_total {}", self.cats.load(Ordering::Relaxed));
+
+        let _ = writeln!(out, "# HELP makea_cat_placeholders_total Placeholder images requested on purpose.");
+        let _ = writeln!(out, "# TYPE makea_cat_placeholders_total counter");
+        let _ = writeln!(out, "makea_cat_placeholders_total {}", self.placeholders.load(Ordering::Relaxed));
+
+        let _ = writeln!(out, "# HELP makea_cat_queued_renders Images waiting to be drawn.");
+        let _ = writeln!(out, "# TYPE makea_cat_queued_renders gauge");
+        let _ = writeln!(out, "makea_cat_queued_renders {}", self.queued_renders.load(Ordering::Relaxed));
+
+        self.render_duration.render(&mut out, "makea_cat_render_duration_seconds", "Time spent drawing cats.");
+        self.cat_latency.render(&mut out, "makea_cat_cat_latency_seconds", "Time taken by cat requests, including waiting to be drawn.");
+
+        let _ = writeln!(out, "# HELP makea_cat_rejections_total Cat requests rejected by the anticheat.");
+        let _ = writeln!(out, "# TYPE makea_cat_rejections_total counter");
+        for reason in RejectReason::ALL {
+            let count = self.rejections[reason as usize].load(Ordering::Relaxed);
+            let _ = writeln!(out, "makea_cat_rejections_total{{reason=\"{reason}\"}} {count}");
+        }
+
+        out
+    }
+}
+
+/// The metrics at some point in time, for the admin endpoints.
+#[derive(Debug, Clone, Serialize)]
+pub struct Snapshot {
+    pub cats: u64,
+    pub placeholders: u64,
+    pub queued_renders: u64,
+    /// The number of rejections for each [RejectReason].
+    pub rejections: BTreeMap<String, u64>,
+}
+
+/// Returns the metrics for this server.
+pub fn metrics() -> &'static Metrics {
+    static METRICS: Metrics = Metrics::new();
+
+    &METRICS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_buckets() {
+        let histogram = Histogram::new();
+        for micros in [100, 500, 501, 1_200, 3_000, 9_999, 60_000] {
+            histogram.observe(Duration::from_micros(micros));
+        }
+
+        // Each bound includes itself, and the last duration is past every one
+        let (buckets, count) = histogram.cumulative();
+        assert_eq!(buckets, [(500, 2), (1_000, 3), (2_000, 4), (5_000, 5), (10_000, 6), (50_000, 6)]);
+        assert_eq!(count, 7);
+
+        let mut out = String::new();
+        histogram.render(&mut out, "test_seconds", "Test.");
+        assert!(out.contains("test_seconds_bucket{le=\"0.0005\"} 2\n"));
+        assert!(out.contains("test_seconds_bucket{le=\"0.05\"} 6\n"));
+        assert!(out.contains("test_seconds_bucket{le=\"+Inf\"} 7\n"));
+        assert!(out.contains("test_seconds_sum 0.0753\n"));
+        assert!(out.contains("test_seconds_count 7\n"));
+
+        histogram.reset();
+        assert_eq!(histogram.cumulative(), (BUCKETS.map(|bound| (bound, 0)), 0));
+    }
+
+    #[test]
+    fn rejections_by_reason() {
+        let metrics = Metrics::new();
+        metrics.reject(RejectReason::WrongTime);
+        metrics.reject(RejectReason::WrongTime);
+        metrics.reject(RejectReason::Replay);
+        metrics.render_duration.observe(Duration::from_millis(1));
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.rejections["wrong_time"], 2);
+        assert_eq!(snapshot.rejections["replay"], 1);
+        assert_eq!(snapshot.rejections["sold_out"], 0);
+        assert!(metrics.render().contains("makea_cat_rejections_total{reason=\"wrong_time\"} 2\n"));
+        assert!(metrics.render().contains("makea_cat_render_duration_seconds_count 1\n"));
+
+        metrics.reset();
+        assert!(metrics.snapshot().rejections.values().all(|&count| count == 0));
+    }
+}
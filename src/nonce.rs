@@ -0,0 +1,87 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+
+use crate::config::Config;
+
+/// How long each nonce is handed out for, in seconds. A nonce is still
+/// accepted for one more bucket after that, so a page loaded right before the
+/// bucket changes still works.
+const BUCKET: i64 = 30;
+
+/// Makes the nonce for the index page to send back with its cat request, or
+/// `None` if there's no `CAT_SECRET` (so nonces aren't needed).
+///
+/// A nonce is an HMAC of the current [BUCKET] of time, so it can't be made
+/// without the secret, and it stops working shortly after the page was loaded.
+/// Static URLs stop working along with it.
+pub fn issue(config: &Config, now: DateTime<Utc>) -> Option<String> {
+    let secret = config.secret.as_ref()?;
+
+    Some(sign(secret.as_bytes(), now.timestamp().div_euclid(BUCKET)))
+}
+
+/// Checks a nonce sent with a cat request. Any request is fine if there's no
+/// `CAT_SECRET`.
+pub fn check(config: &Config, nonce: Option<&str>, now: DateTime<Utc>) -> bool {
+    let Some(secret) = &config.secret else {
+        return true;
+    };
+
+    let Some(nonce) = nonce.and_then(hex) else {
+        return false;
+    };
+
+    // The MAC is compared in constant time, so it can't be guessed a byte at a time
+    let bucket = now.timestamp().div_euclid(BUCKET);
+    [bucket, bucket - 1].into_iter().any(|bucket| mac(secret.as_bytes(), bucket).verify_slice(&nonce).is_ok())
+}
+
+/// Signs a bucket of time with HMAC-SHA1, as hex.
+fn sign(key: &[u8], bucket: i64) -> String {
+    mac(key, bucket).finalize().into_bytes().iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Starts an HMAC-SHA1 of a bucket of time, ready to finish or verify.
+fn mac(key: &[u8], bucket: i64) -> Hmac<Sha1> {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(&bucket.to_be_bytes());
+    mac
+}
+
+/// Reads a nonce's hex back into bytes, or `None` if it isn't hex.
+fn hex(nonce: &str) -> Option<Vec<u8>> {
+    if !nonce.len().is_multiple_of(2) {
+        return None;
+    }
+
+    let digit = |byte: u8| char::from(byte).to_digit(16);
+    nonce.as_bytes().chunks(2).map(|pair| Some((digit(pair[0])? * 16 + digit(pair[1])?) as u8)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+
+    #[test]
+    fn nonces_last_two_buckets() {
+        let config = Config { secret: Some("secret".into()), ..Config::default() };
+        let now = "2024-01-01T02:22:30Z".parse::<DateTime<Utc>>().unwrap();
+        let nonce = issue(&config, now).unwrap();
+
+        // HMAC-SHA1 of the bucket, as 40 hex digits
+        assert_eq!(nonce.len(), 40);
+        assert!(check(&config, Some(&nonce), now));
+        assert!(check(&config, Some(&nonce), now + TimeDelta::seconds(BUCKET)));
+        assert!(!check(&config, Some(&nonce), now + TimeDelta::seconds(2 * BUCKET)));
+
+        let tampered = format!("{}{}", if nonce.starts_with('0') { '1' } else { '0' }, &nonce[1..]);
+        assert!(!check(&config, Some(&tampered), now));
+        assert!(!check(&config, Some("made-up"), now));
+        assert!(!check(&config, Some("é"), now));
+        assert!(!check(&config, None, now));
+        assert!(check(&Config::default(), None, now));
+    }
+}
@@ -0,0 +1,117 @@
+use axum::{body::{to_bytes, Body}, extract::Request, http::{header::{ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, IF_RANGE, RANGE}, HeaderValue, StatusCode}, middleware::Next, response::{IntoResponse, Response}};
+use log::error;
+use sha1::{Digest, Sha1};
+
+/// Answers `Range` requests for images with `206 Partial Content`, so clients
+/// fetching big cats in pieces get the pieces they asked for.
+///
+/// Most cats are random, so the next request for the same URL is a different
+/// image. Every image gets a strong `ETag` made from its bytes, and a range
+/// request whose `If-Range` doesn't match it gets the whole (new) image
+/// instead of a piece of it, so pieces of different cats never get stitched
+/// together.
+///
+/// The whole image is still drawn; only the part that's sent changes. Only a
+/// single range is supported, and requests for several ranges just get the
+/// whole image, which HTTP allows.
+pub async fn serve_ranges(request: Request, next: Next) -> Response {
+    let range = request.headers().get(RANGE).cloned();
+    let if_range = request.headers().get(IF_RANGE).cloned();
+
+    let response = next.run(request).await;
+
+    let image = response.status() == StatusCode::OK
+        && response.headers().get(CONTENT_TYPE).is_some_and(|kind| kind.as_bytes().starts_with(b"image/"));
+
+    if !image {
+        return response;
+    }
+
+    // Images are already fully in memory, so this doesn't cost anything
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            error!("Couldn't read image for range request: {err}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let etag = etag(&bytes);
+    parts.headers.insert(ETAG, etag.parse().unwrap());
+    parts.headers.insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+
+    // Only a range of this exact image can be sent. Dates aren't supported in
+    // `If-Range`, since images have no modification time, so they never match.
+    let Some(range) = range.filter(|_| if_range.is_none_or(|tag| tag.as_bytes() == etag.as_bytes())) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+
+    let length = bytes.len() as u64;
+
+    match range.to_str().ok().and_then(|range| parse_range(range, length)) {
+        Some(Ok((start, end))) => {
+            parts.status = StatusCode::PARTIAL_CONTENT;
+            parts.headers.insert(CONTENT_RANGE, format!("bytes {start}-{end}/{length}").parse().unwrap());
+            parts.headers.remove(CONTENT_LENGTH);
+
+            Response::from_parts(parts, Body::from(bytes.slice(start as usize..=end as usize)))
+        }
+        Some(Err(())) => (
+            StatusCode::RANGE_NOT_SATISFIABLE,
+            [(CONTENT_RANGE, format!("bytes */{length}"))],
+        ).into_response(),
+        // Ranges that can't be understood are ignored
+        None => Response::from_parts(parts, Body::from(bytes)),
+    }
+}
+
+/// A strong entity tag for a body, which is a hash of its bytes.
+fn etag(body: &[u8]) -> String {
+    let hash = Sha1::digest(body).iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+
+    format!(r#""{hash}""#)
+}
+
+/// Parses a single `bytes=` range for a body with the given length, returning
+/// the first and last byte (inclusive).
+///
+/// Returns `None` if the range can't be understood, and `Some(Err(()))` if it
+/// doesn't overlap the body at all.
+fn parse_range(range: &str, length: u64) -> Option<Result<(u64, u64), ()>> {
+    let range = range.trim().strip_prefix("bytes=")?;
+
+    if range.contains(',') {
+        return None;
+    }
+
+    let (start, end) = range.split_once('-')?;
+    let (start, end) = (start.trim(), end.trim());
+
+    let (start, end) = if start.is_empty() {
+        // A suffix, like the last 500 bytes
+        let suffix = end.parse::<u64>().ok()?;
+        if suffix == 0 {
+            return Some(Err(()));
+        }
+
+        (length.saturating_sub(suffix), length.checked_sub(1)?)
+    } else {
+        let start = start.parse::<u64>().ok()?;
+        let end = if end.is_empty() { u64::MAX } else { end.parse::<u64>().ok()? };
+
+        if end < start {
+            return None;
+        }
+
+        (start, end.min(length.saturating_sub(1)))
+    };
+
+    if start >= length {
+        return Some(Err(()));
+    }
+
+    Some(Ok((start, end)))
+}
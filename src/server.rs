@@ -0,0 +1,1235 @@
+use std::{convert::Infallible, net::IpAddr, sync::{atomic::{AtomicBool, AtomicU64, Ordering}, Arc, Mutex, PoisonError}, time::{Duration, Instant}};
+
+use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use axum::{body::Body, extract::{ConnectInfo, FromRef, Path, Query, RawQuery, State}, http::{header::{ACCEPT, CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_TYPE, ETAG, IF_NONE_MATCH}, HeaderMap, HeaderValue, Request, StatusCode}, middleware::{self, Next}, response::{IntoResponse, Response}, routing::get, Json, Router};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeDelta, Utc};
+use futures_util::{stream, StreamExt};
+use log::{error, info, warn};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast::error::RecvError, Semaphore};
+use tower::ServiceBuilder;
+use tower_http::{limit::RequestBodyLimitLayer, timeout::TimeoutLayer};
+use crate::{access, admin, config::Config, connection::{self, ClientIp, Connection}, draw::{self, CanvasError, CatBuilder, CatFeatures, CatOptions, Format, Variant}, live::{self, Availability}, metrics::Metrics, nonce, range};
+use crate::time::{available_anywhere, correct_time, correct_time_for_query, current_window, format_offset, next_window, offset_allowed, parse_query, try_parse_query, valid_time_in_zone, valid_time_offsets, verify_time, Anticheat, Cooldown, QueryError, RejectReason, Stock, StandardAnticheat, TimeUnit};
+
+/// What's drawn instead of a cat during maintenance.
+const MAINTENANCE_MESSAGE: &str = "be right back / ara tornem";
+
+/// The most sizes a single `/cat?sizes=` request can ask for.
+const MAX_SIZES: usize = 4;
+
+/// How often a new cat is sent on `/cat/stream`.
+const STREAM_INTERVAL: Duration = Duration::from_secs(3);
+
+/// The longest a single `/cat/stream` response can last.
+const STREAM_DURATION: Duration = Duration::from_secs(60);
+
+/// Makes the app with every route and layer, ready to be served with
+/// [Connection](connection::Connection) info (see [Router::into_make_service_with_connect_info]).
+pub fn build_router(state: AppState) -> Router {
+    Router::new()
+        .route("/cat", get(|State(state): State<AppState>, ClientIp(ip): ClientIp, Query(options): Query<CatOptions>, Query(sizes): Query<SizesQuery>, request: Request<Body>| async move {
+            // API clients would rather hear about their typos than get a placeholder
+            if wants_json(request.headers()) {
+                let parsed = request.uri().query().ok_or(QueryError::Missing).and_then(try_parse_query);
+
+                if let Err(error) = parsed {
+                    state.metrics.reject(RejectReason::MalformedQuery);
+                    return bad_query(error);
+                }
+            }
+
+            let now = Utc::now();
+            match correct_time_for_query(state.config, state.anticheat.as_ref(), ip, request.uri().query(), now).await {
+                Ok(()) => {
+                    let offset = request.uri().query().and_then(parse_query).map_or(0, |(_, offset)| offset);
+                    cat_once(&state, ip, offset, now, options, sizes.sizes).await
+                }
+                Err(reason) => out_of_stock(&state, OutOfStock::Rejected(reason), None, None, options.border).await,
+            }
+        }).post(order_cat))
+        .route("/cat/stream", get(cat_stream))
+        .route("/cat/for/:name", get(|State(state): State<AppState>, Path(name): Path<String>, Query(options): Query<CatOptions>| async move {
+            // Named cats are a showcase, so they're always available
+            cat(&state, CatOptions { seed: Some(draw::name_seed(&name)), ..options }).await
+        }))
+        .route("/cat/daily", get(|State(state): State<AppState>, Query(options): Query<CatOptions>| async move {
+            daily(&state, Utc::now().date_naive(), options).await
+        }))
+        .route("/cat/daily/:date", get(|State(state): State<AppState>, Path(date): Path<NaiveDate>, Query(options): Query<CatOptions>| async move {
+            daily(&state, date, options).await
+        }))
+        .route("/discountcat", get(|State(state): State<AppState>| async move {
+            // I changed the actual URL for this endpoint on the version I'm hosting.
+            // Don't try to cheat cats in >:3
+        
+            warn!("Free cat endpoint was hit - giving away a free cat!");
+            cat(&state, CatOptions::default()).await
+        }))
+        // Cats are expensive, so each connection only gets so many
+        .route_layer(middleware::from_fn_with_state(state.clone(), connection::limit_cats))
+        .route_layer(middleware::from_fn_with_state(state.clone(), maintenance))
+        .route("/", get(index))
+        .route("/torna", get(torna))
+        .route("/cat/describe", get(describe))
+        .route("/cat/archive", get(archive))
+        .route("/verify", get(verify))
+        .route("/zones", get(zones))
+        .route("/status", get(status))
+        .route("/robots.txt", get(robots))
+        .route("/sitemap.xml", get(sitemap))
+        .route("/metrics", get(|State(state): State<AppState>| async move { state.metrics.render() }))
+        .route("/version", get(version))
+        .route("/ws", get(live::socket))
+        .nest("/admin", admin::router(state.clone()))
+        // .fallback(get(routes::error404()));
+        .layer(middleware::from_fn(range::serve_ranges))
+        // The operational limits for every request, outermost first:
+        // - every request is logged, even ones that were limited
+        // - requests (like pathologically slow cats) that take too long give up
+        //   with `503 Service Unavailable`, including time spent waiting below
+        // - only so many requests are handled at once, and the rest wait
+        // - big request bodies get `413 Payload Too Large`
+        .layer(ServiceBuilder::new()
+            .layer(middleware::from_fn_with_state(state.clone(), access::log_request))
+            .layer(TimeoutLayer::with_status_code(StatusCode::SERVICE_UNAVAILABLE, state.config.timeout))
+            .layer(middleware::from_fn_with_state(Arc::new(Semaphore::new(state.config.concurrent_requests)), connection::limit_requests))
+            .map_response(IntoResponse::into_response)
+            .layer(RequestBodyLimitLayer::new(state.config.max_body)))
+        .with_state(state)
+}
+
+/// Everything shared between requests.
+#[derive(Clone)]
+pub struct AppState {
+    /// The settings for this instance.
+    pub(crate) config: &'static Config,
+
+    /// Counts what the server has been doing.
+    pub(crate) metrics: &'static Metrics,
+
+    /// Lets only so many images be drawn at once (see [render]).
+    renders: Arc<Semaphore>,
+
+    /// Picks the seeds for cats that weren't asked for with one.
+    seeds: Arc<Mutex<ChaCha8Rng>>,
+
+    /// Decides who gets cats.
+    anticheat: Arc<dyn Anticheat>,
+
+    /// Remembers who got a cat this window, if each client only gets one.
+    cooldown: Option<Arc<Cooldown>>,
+
+    /// How many cats are left this window, if there's only so many.
+    stock: Option<Arc<Stock>>,
+
+    /// Whether cats are paused, so every cat request gets the maintenance
+    /// image instead.
+    maintenance: Arc<AtomicBool>,
+
+    /// Tells `/ws` clients when cats become available or unavailable.
+    availability: Availability,
+}
+
+impl AppState {
+    /// Makes the state for a server with the given config, reporting to the
+    /// given metrics.
+    pub fn new(config: &'static Config, metrics: &'static Metrics) -> Self {
+        let seeds = match config.seed {
+            Some(seed) => ChaCha8Rng::seed_from_u64(seed),
+            None => ChaCha8Rng::from_entropy(),
+        };
+
+        let availability = Availability::watch(config);
+
+        let stock = config.stock_per_window.map(|per_window| Arc::new(Stock::new(per_window)));
+        if let Some(stock) = &stock {
+            tokio::spawn(restock(stock.clone(), availability.clone()));
+        }
+
+        Self {
+            config,
+            metrics,
+            renders: Arc::new(Semaphore::new(config.concurrent_renders)),
+            seeds: Arc::new(Mutex::new(seeds)),
+            anticheat: Arc::new(StandardAnticheat::new(config)),
+            cooldown: config.one_per_window.then(Arc::default),
+            stock,
+            maintenance: Arc::new(AtomicBool::new(config.maintenance)),
+            availability,
+        }
+    }
+
+    /// Replaces what decides who gets cats.
+    pub fn with_anticheat(self, anticheat: Arc<dyn Anticheat>) -> Self {
+        Self { anticheat, ..self }
+    }
+
+    /// Picks a seed for a new random cat.
+    fn seed(&self) -> u64 {
+        self.seeds.lock().unwrap_or_else(PoisonError::into_inner).gen()
+    }
+}
+
+impl FromRef<AppState> for &'static Config {
+    fn from_ref(state: &AppState) -> Self {
+        state.config
+    }
+}
+
+impl FromRef<AppState> for Availability {
+    fn from_ref(state: &AppState) -> Self {
+        state.availability.clone()
+    }
+}
+
+/// Fills the stock back up every time a new window starts.
+async fn restock(stock: Arc<Stock>, availability: Availability) {
+    let mut changes = availability.subscribe();
+
+    loop {
+        let available = match changes.recv().await {
+            Ok(available) => available,
+            // Missed some changes, so just check whether it's a window now
+            Err(RecvError::Lagged(_)) => availability.now(),
+            Err(RecvError::Closed) => return,
+        };
+
+        if available {
+            info!("Restocking cats, with {} left over", stock.left());
+            stock.refill();
+        }
+    }
+}
+
+/// Turns away every cat request while the server is in maintenance mode.
+async fn maintenance(State(state): State<AppState>, request: Request<Body>, next: Next) -> Response {
+    if state.maintenance.load(Ordering::Relaxed) {
+        return out_of_stock(&state, OutOfStock::Maintenance, None, None, 0).await;
+    }
+
+    next.run(request).await
+}
+
+/// Toggles maintenance mode every time the server gets `SIGUSR1`, so cats can
+/// be paused without restarting.
+#[cfg(unix)]
+pub async fn toggle_maintenance(state: AppState) {
+    let maintenance = state.maintenance;
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut signals = match signal(SignalKind::user_defined1()) {
+        Ok(signals) => signals,
+        Err(err) => {
+            error!("Couldn't listen for SIGUSR1, so maintenance mode can't be toggled: {err}");
+            return;
+        }
+    };
+
+    while signals.recv().await.is_some() {
+        let paused = !maintenance.fetch_xor(true, Ordering::Relaxed);
+        warn!("Maintenance mode is now {}", if paused { "on" } else { "off" });
+    }
+}
+
+/// The index page. This will generate a random background color for the client,
+/// and will send JavaScript only if it is a valid time somewhere.
+/// 
+/// While it isn't a valid time anywhere, the page has an `ETag`, so clients
+/// polling the page can cheaply tell when a cat becomes available. A client
+/// revalidating its cached page will keep its old background until then.
+///
+/// Clients without JavaScript get a cat too, with a time and offset picked by
+/// the server. Asking for the same time and offset twice is a replay, so while
+/// cats are available the page is never cached (`Cache-Control: no-store`),
+/// and reloading it always gives a new URL.
+///
+/// The page is in both English and Catalan, unless just one is picked with
+/// `?lang=en` or `?lang=ca`.
+async fn index(State(state): State<AppState>, Query(query): Query<IndexQuery>, headers: HeaderMap) -> Response {
+    let config = state.config;
+    let lang = query.lang.as_deref().and_then(Lang::parse);
+
+    // Figure out if it's the correct time anywhere (or pretend it is, for
+    // testing the page)
+    let now = Utc::now();
+    let valid = available_anywhere(config, now) || config.always_js;
+
+    let etag = r#""unavailable""#;
+
+    // Nothing has changed, so the client can use the page it has
+    let cached = !valid && headers.get_all(IF_NONE_MATCH).iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|tag| tag.trim() == etag || tag.trim() == "*");
+
+    if cached {
+        return (
+            StatusCode::NOT_MODIFIED,
+            [(ETAG, etag), (CACHE_CONTROL, "no-cache")],
+        ).into_response();
+    }
+
+    // Generate the background color
+    let mut rng = rand::thread_rng();
+    let range = config.color_min as u32..=config.color_max as u32;
+    let background = (rng.gen_range(range.clone()) << 16) + (rng.gen_range(range.clone()) << 8) + (rng.gen_range(range));
+    let foreground = match config.outline {
+        Some(outline) => format!("{:02x}{:02x}{:02x}", outline.r, outline.g, outline.b),
+        None if config.dark() => "fff".into(),
+        None => "000".into(),
+    };
+
+    // The default messages are in English and then Catalan
+    let messages = config.messages().collect::<Vec<_>>();
+    let come_back = match lang.and_then(|lang| messages.get(lang.variant() as usize)) {
+        Some(message) => message.clone(),
+        None => messages.join(" / "),
+    };
+    let torna = match lang.map(Lang::variant) {
+        Some(Variant::ComeBack) => "?variant=come_back",
+        Some(Variant::Torna) => "?variant=torna",
+        None => "",
+    };
+
+    let make_a_cat = Lang::say(lang, "make a cat", "fer un gat");
+    let alt = Lang::say(lang, "a randomly generated cat", "un gat generat a l'atzar");
+    let html_lang = lang.map(|lang| format!(r#" lang="{}""#, lang.code())).unwrap_or_default();
+    let (width, height) = (config.width, config.height);
+
+    // Generate index.html (with inline JS).
+    // The JS and HTML were somewhat code golfed, but they were kept looking
+    // somewhat normal in case further changes need to be made :)
+    let index = if valid {
+        // Cats have to be asked for with a nonce from the server, if it's set up
+        let nonce = nonce::issue(config, now).map(|nonce| format!("&nonce={nonce}")).unwrap_or_default();
+
+        // The cat is fetched (instead of just setting the src) so its alt text
+        // can be read from the headers
+        let (hour, minute) = (config.hour, config.minute);
+        let now_make_a_cat = Lang::say(lang, &format!("{hour}:{minute:0>2} make a cat"), &format!("{hour}:{minute:0>2} fer un gat"));
+        let hour12 = hour % 12;
+        let js = &format!(r#"<script>a=new Date();{hour12}-a.getHours()%12|{minute}-a.getMinutes()?d.src="/torna{torna}":(e.textContent="{now_make_a_cat}",fetch(`/cat?${{a.getTime()}}&`+a.getTimezoneOffset()+"{nonce}").then(r=>(d.alt=r.headers.get("x-cat-alt")||d.alt,r.blob())).then(b=>d.src=URL.createObjectURL(b)))</script>"#);
+
+        // Without JavaScript, use a time and offset where it's the right time
+        let noscript = valid_time_offsets().iter()
+            .find(|&&offset| verify_time(config, now.timestamp_millis(), offset, None, now).is_ok())
+            .map(|offset| format!(r#"<noscript><img src="/cat?{}&{offset}{nonce}" alt="{alt}"></noscript>"#, now.timestamp_millis()))
+            .unwrap_or_default();
+
+        format!(r#"<!DOCTYPE html><html{html_lang}><head><title>makea.cat</title></head><body style="text-align:center;background-color:#{background:0>6x};color:#{foreground}"><p>{make_a_cat}</p><div style="margin:0 auto;width:{width}px;height:{height}px;border:1px solid#{foreground}">{noscript}<img src="" alt="{alt}" id="d"></div><p id="e">{come_back}</p>{js}</body></html>"#)
+    } else {
+        // Tell the client when and where the next window is
+        let next = next_window(config, now).map(|(offset, until)| {
+            let offset = format_offset(offset);
+            let minutes = (until.num_seconds() + 59) / 60;
+            let until = if minutes < 60 {
+                format!("{minutes} min")
+            } else {
+                format!("{} h {} min", minutes / 60, minutes % 60)
+            };
+
+            let next = Lang::say(lang, &format!("next cat window: {offset} in {until}"), &format!("propera finestra de gats: {offset} d'aquí a {until}"));
+            format!("<p>{next}</p>")
+        }).unwrap_or_default();
+
+        format!(r#"<!DOCTYPE html><html{html_lang}><head><title>makea.cat</title></head><body style="text-align:center;background-color:#{background:0>6x};color:#{foreground}"><p>{make_a_cat}</p><div style="margin:0 auto;width:{width}px;height:{height}px;border:1px solid#{foreground}"><img src="/torna{torna}" alt="{come_back}"></div><p>{come_back}</p>{next}</body></html>"#)
+    };
+
+    // Turn it into a response, which can't be reused while it has a cat URL
+    if valid {
+        return (
+            StatusCode::OK,
+            [(CONTENT_TYPE, "text/html"), (CACHE_CONTROL, "no-store")],
+            index,
+        ).into_response();
+    }
+
+    (
+        StatusCode::OK,
+        [(CONTENT_TYPE, "text/html"), (ETAG, etag), (CACHE_CONTROL, "no-cache")],
+        index,
+    ).into_response()
+}
+
+/// The query for the index page.
+#[derive(Deserialize)]
+struct IndexQuery {
+    /// The only language to show, like `en` or `ca`. Anything else shows both.
+    lang: Option<String>,
+}
+
+/// A language the index page can be shown in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Lang {
+    English,
+    Catalan,
+}
+
+impl Lang {
+    /// Reads a language code, like `en` or `ca`.
+    fn parse(code: &str) -> Option<Self> {
+        match code.trim().to_lowercase().as_str() {
+            "en" => Some(Lang::English),
+            "ca" => Some(Lang::Catalan),
+            _ => None,
+        }
+    }
+
+    /// The language's code, for the `lang` attribute.
+    fn code(self) -> &'static str {
+        match self {
+            Lang::English => "en",
+            Lang::Catalan => "ca",
+        }
+    }
+
+    /// The default "come back" message that's in this language.
+    fn variant(self) -> Variant {
+        match self {
+            Lang::English => Variant::ComeBack,
+            Lang::Catalan => Variant::Torna,
+        }
+    }
+
+    /// Picks the text for the given language, or both if there isn't one.
+    fn say(lang: Option<Lang>, english: &str, catalan: &str) -> String {
+        match lang {
+            Some(Lang::English) => english.into(),
+            Some(Lang::Catalan) => catalan.into(),
+            None => format!("{english} / {catalan}"),
+        }
+    }
+}
+
+/// Makes a cat.
+/// 
+/// Cats that can't be drawn with the given options are a bad request. The seed
+/// used for a cat is sent back in an `X-Cat-Seed` header, so the same cat can
+/// be requested again with `seed=<seed>`.
+async fn cat(state: &AppState, mut options: CatOptions) -> Response {
+    let start = Instant::now();
+    options.date.get_or_insert_with(|| Utc::now().date_naive());
+
+    // Render the image
+    let seed = options.seed.unwrap_or_else(|| state.seed());
+    let builder = CatBuilder::from(options).seed(seed).config(state.config);
+    let (builder, result) = match render(state, move || {
+        let result = builder.render();
+        (builder, result)
+    }).await {
+        Ok(rendered) => rendered,
+        Err(response) => return response,
+    };
+
+    let cat = match result {
+        Ok(cat) => cat,
+        Err(err) => {
+            info!("Couldn't make cat with {:?}: {err}", builder.options());
+            return (StatusCode::BAD_REQUEST, err.to_string()).into_response();
+        }
+    };
+
+    state.metrics.render_duration.observe(cat.render_time);
+    state.metrics.cats.fetch_add(1, Ordering::Relaxed);
+    state.metrics.cat_latency.observe(start.elapsed());
+    info!("Made cat {seed} in {:?}", start.elapsed());
+
+    let golden = cat.golden();
+    let render_time = cat.render_time;
+    let bounds = cat.bounds;
+
+    // Screen readers can say what the cats look like
+    let alt = cat.features.iter().map(CatFeatures::describe).collect::<Vec<_>>().join("; ");
+
+    // Saved cats get a name that can be used to draw them again
+    let format = cat.format;
+    let mut response = (
+        [("x-cat-seed", seed.to_string()), ("x-cat-alt", alt)],
+        [(CONTENT_DISPOSITION, format!(r#"inline; filename="cat-{seed}.{}""#, format.extension()))],
+        image_response(cat.image, format, start),
+    ).into_response();
+
+    // The whole render (with waiting in line) is already in there, but just the
+    // drawing says more about how expensive the options are
+    let draw = format!("draw;dur={:.1}", render_time.as_secs_f64() * 1000.);
+    response.headers_mut().append("server-timing", HeaderValue::from_str(&draw).expect("timings are valid headers"));
+
+    // Where the cats ended up, for cropping them tightly
+    if let Some(bounds) = bounds {
+        let bounds = format!("{},{},{},{}", bounds.x, bounds.y, bounds.width, bounds.height);
+        response.headers_mut().insert("x-cat-bounds", HeaderValue::from_str(&bounds).expect("numbers are valid headers"));
+    }
+
+    if golden {
+        info!("Cat {seed} is golden!");
+        response.headers_mut().insert("x-cat-rarity", HeaderValue::from_static("golden"));
+    }
+
+    response
+}
+
+/// The body of a `POST /cat`, with the same time and offset as a query along
+/// with any [CatOptions], like `{"time": 1700000000000, "offset": -120,
+/// "count": 2}`.
+#[derive(Deserialize)]
+struct CatOrder {
+    /// The client's time, in milliseconds (or seconds, see `unit`).
+    time: i64,
+    /// The client's time zone offset, in minutes.
+    offset: i64,
+    /// Whether the time is in seconds or milliseconds. It's guessed from the
+    /// time if this is missing.
+    unit: Option<TimeUnit>,
+    /// The client's round-trip time to the server, in milliseconds, if it
+    /// measured one.
+    rtt: Option<i64>,
+    /// The nonce from the index page, if there's a `CAT_SECRET`.
+    nonce: Option<String>,
+    #[serde(flatten)]
+    options: CatOptions,
+}
+
+/// Makes a cat for a JSON body instead of a query, for programs that would
+/// rather not build URLs. It's checked the same way as `GET /cat`.
+async fn order_cat(State(state): State<AppState>, ClientIp(ip): ClientIp, Json(order): Json<CatOrder>) -> Response {
+    let time = TimeUnit::to_millis(order.unit, order.time);
+
+    let now = Utc::now();
+    match correct_time(state.config, state.anticheat.as_ref(), ip, time, order.offset, order.rtt, order.nonce.as_deref(), now) {
+        Ok(()) => cat_once(&state, ip, order.offset, now, order.options, None).await,
+        Err(reason) => out_of_stock(&state, OutOfStock::Rejected(reason), None, None, order.options.border).await,
+    }
+}
+
+/// Makes a cat for a client that passed the anticheat (at several `sizes`, if
+/// there are any), unless it already got one in this window and there's a
+/// [Cooldown], or there's a [Stock] and it's run out.
+///
+/// Cats that were made say where it was the right time when the client was
+/// checked at `now` in an `X-Cat-Window` header, like `+05:30`, even if the
+/// window closed while the cat was being drawn.
+async fn cat_once(state: &AppState, ip: IpAddr, offset: i64, now: DateTime<Utc>, options: CatOptions, sizes: Option<Vec<u32>>) -> Response {
+    let make = |options| async move {
+        match sizes {
+            Some(sizes) => cat_sizes(state, options, sizes).await,
+            None => cat(state, options).await,
+        }
+    };
+
+    let window = current_window(state.config, now);
+
+    if let Some(cooldown) = &state.cooldown {
+        if !cooldown.claim(ip, offset, now) {
+            return out_of_stock(state, OutOfStock::Rejected(RejectReason::Cooldown), None, None, options.border).await;
+        }
+    }
+
+    if let Some(stock) = &state.stock {
+        if !stock.take() {
+            if let Some(cooldown) = &state.cooldown {
+                cooldown.release(ip, offset);
+            }
+
+            return out_of_stock(state, OutOfStock::Rejected(RejectReason::SoldOut), None, None, options.border).await;
+        }
+    }
+
+    // Cats that couldn't be made don't count
+    let mut response = make(options).await;
+    if response.status() != StatusCode::OK {
+        if let Some(cooldown) = &state.cooldown {
+            cooldown.release(ip, offset);
+        }
+        if let Some(stock) = &state.stock {
+            stock.give_back();
+        }
+    } else if let Some(window) = window {
+        // Where it's the right time, like `+05:30`
+        let window = format_offset(window);
+        let window = window.trim_start_matches("UTC");
+        response.headers_mut().insert("x-cat-window", HeaderValue::from_str(window).expect("offsets are valid headers"));
+    }
+
+    response
+}
+
+/// The options for `/cat` on top of the [CatOptions].
+#[derive(Deserialize)]
+struct SizesQuery {
+    /// The widths to draw the same cat at, like `400,800,1200`, instead of
+    /// sending a single image.
+    #[serde(default, deserialize_with = "widths")]
+    sizes: Option<Vec<u32>>,
+}
+
+/// Reads a comma-separated list of widths, like `400,800`.
+fn widths<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<u32>>, D::Error> {
+    let text = String::deserialize(deserializer)?;
+
+    text.split(',')
+        .map(|width| width.trim().parse().map_err(serde::de::Error::custom))
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
+
+/// The same cat drawn at several sizes, from `/cat?sizes=`.
+#[derive(Serialize)]
+struct Sizes {
+    seed: u64,
+    images: Vec<SizedCat>,
+    /// Every image with its width, ready to be put in an `<img srcset>`.
+    srcset: String,
+}
+
+/// A single size of a cat.
+#[derive(Serialize)]
+struct SizedCat {
+    width: u32,
+    height: u32,
+    /// The image itself, as a `data:` URL.
+    url: String,
+}
+
+/// Draws the same cat at each of the `sizes`, for browsers to pick from with
+/// `srcset`. Each width has to be a multiple of the configured width, since
+/// bigger cats are drawn with a bigger `scale`, and all the sizes together
+/// can't have more than [Config::max_pixels].
+async fn cat_sizes(state: &AppState, mut options: CatOptions, sizes: Vec<u32>) -> Response {
+    let start = Instant::now();
+    let width = state.config.width as u32;
+
+    if sizes.is_empty() || sizes.len() > MAX_SIZES {
+        return (StatusCode::BAD_REQUEST, format!("there can be 1 to {MAX_SIZES} sizes")).into_response();
+    }
+
+    if let Some(size) = sizes.iter().find(|&&size| size == 0 || size % width != 0) {
+        return (StatusCode::BAD_REQUEST, format!("{size} isn't a multiple of the width, {width}")).into_response();
+    }
+
+    // Each size is fine on its own, but they're all drawn for one request
+    let (height, count) = (state.config.height as u64, options.count as u64);
+    let pixels = sizes.iter()
+        .map(|&size| (size as u64).saturating_mul(height * (size / width) as u64).saturating_mul(count))
+        .fold(0u64, u64::saturating_add);
+    if pixels > state.config.max_pixels {
+        return (StatusCode::BAD_REQUEST, CanvasError::TooBig.to_string()).into_response();
+    }
+
+    // Every size uses the same seed (and day), so they're all the same cat
+    let seed = options.seed.unwrap_or_else(|| state.seed());
+    options.date.get_or_insert_with(|| Utc::now().date_naive());
+    let builders = sizes.iter()
+        .map(|size| CatBuilder::from(CatOptions { scale: size / width, ..options.clone() }).seed(seed).config(state.config))
+        .collect::<Vec<_>>();
+
+    let result = match render(state, move || builders.iter().map(CatBuilder::render).collect::<Result<Vec<_>, _>>()).await {
+        Ok(result) => result,
+        Err(response) => return response,
+    };
+
+    let cats = match result {
+        Ok(cats) => cats,
+        Err(err) => {
+            info!("Couldn't make cat at sizes {sizes:?} with {options:?}: {err}");
+            return (StatusCode::BAD_REQUEST, err.to_string()).into_response();
+        }
+    };
+
+    state.metrics.cats.fetch_add(1, Ordering::Relaxed);
+    state.metrics.cat_latency.observe(start.elapsed());
+    info!("Made cat {seed} at {} sizes in {:?}", sizes.len(), start.elapsed());
+
+    for cat in &cats {
+        state.metrics.render_duration.observe(cat.render_time);
+    }
+
+    let (width, height) = (state.config.width as u32, state.config.height as u32);
+    let images = sizes.iter().zip(cats)
+        .map(|(&size, cat)| SizedCat {
+            width: size,
+            height: height * (size / width),
+            url: format!("data:{};base64,{}", cat.format.content_type(), STANDARD.encode(cat.image)),
+        })
+        .collect::<Vec<_>>();
+
+    let srcset = images.iter()
+        .map(|image| format!("{} {}w", image.url, image.width))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    Json(Sizes { seed, images, srcset }).into_response()
+}
+
+/// What the cats for a seed look like, from `/cat/describe`.
+#[derive(Serialize)]
+struct Description {
+    seed: u64,
+    cats: Vec<CatDescription>,
+    /// How long the cats took to draw, in milliseconds, if they were drawn.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    render_ms: Option<f64>,
+}
+
+/// The options for `/cat/describe` on top of the [CatOptions].
+#[derive(Deserialize)]
+struct DescribeQuery {
+    /// Whether to actually draw the cats, to see how long they take.
+    #[serde(default, deserialize_with = "draw::switch")]
+    timed: bool,
+}
+
+/// What a single cat looks like.
+#[derive(Serialize)]
+struct CatDescription {
+    #[serde(flatten)]
+    features: CatFeatures,
+    description: String,
+}
+
+/// Describes the cats that would be drawn with the given options (usually
+/// just a seed), without drawing them. With `timed=1` they're drawn anyway
+/// (and thrown away), to find out which options make cats slow.
+async fn describe(State(state): State<AppState>, Query(mut options): Query<CatOptions>, Query(query): Query<DescribeQuery>) -> Response {
+    let seed = options.seed.unwrap_or_else(|| state.seed());
+    options.date.get_or_insert_with(|| Utc::now().date_naive());
+
+    let config = state.config;
+    let features = match draw::roll_features(&options, seed, config) {
+        Ok(features) => features,
+        Err(err) => return (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+    };
+
+    let render_ms = if query.timed {
+        match render(&state, move || draw::purchase_cat(&options, seed, config)).await {
+            Ok(Ok(cat)) => {
+                state.metrics.render_duration.observe(cat.render_time);
+                Some(cat.render_time.as_secs_f64() * 1000.)
+            }
+            Ok(Err(err)) => return (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+            Err(response) => return response,
+        }
+    } else {
+        None
+    };
+
+    let cats = features.into_iter()
+        .map(|features| CatDescription { description: features.describe(), features })
+        .collect();
+
+    Json(Description { seed, cats, render_ms }).into_response()
+}
+
+/// Why a client is being told to come back later instead of getting a cat.
+#[derive(Debug, Clone, Copy)]
+enum OutOfStock {
+    /// The client asked for the placeholder image, like from `/torna`. This is
+    /// completely normal.
+    Placeholder,
+    /// The client asked for a cat but was rejected by the anticheat, which
+    /// might mean they're cheating.
+    Rejected(RejectReason),
+    /// Cats are paused for maintenance, so nobody gets one.
+    Maintenance,
+}
+
+/// Tells the client to come back later, optionally with a specific message,
+/// keeping track of why. Where the message goes is random unless there's a
+/// `seed`.
+///
+/// The response has an `X-Cat-Availability` header saying whether it's the
+/// right time anywhere (`available`) or nowhere (`unavailable`), so a rejected
+/// client can tell whether to wait or to fix its clock.
+async fn out_of_stock(state: &AppState, why: OutOfStock, variant: Option<Variant>, seed: Option<u64>, border: u32) -> Response {
+    let start = Instant::now();
+
+    match why {
+        OutOfStock::Placeholder => {
+            state.metrics.placeholders.fetch_add(1, Ordering::Relaxed);
+        }
+        OutOfStock::Rejected(reason) => {
+            state.metrics.reject(reason);
+            info!("Rejected cat request ({reason})");
+        }
+        OutOfStock::Maintenance => {
+            info!("Turned away cat request for maintenance");
+        }
+    }
+
+    let seed = seed.unwrap_or_else(|| state.seed());
+    let config = state.config;
+    let draw = move || match why {
+        OutOfStock::Maintenance => draw::message(config, MAINTENANCE_MESSAGE, seed, 0),
+        // Developers would rather see what went wrong
+        OutOfStock::Rejected(reason) if config.debug_reasons => draw::message(config, &format!("rejected: {reason}"), seed, border),
+        // Cheaters get a sad cat instead of a message
+        OutOfStock::Rejected(reason) if reason.suspicious() => draw::nice_try(config, seed, border),
+        _ => draw::out_of_stock(config, variant, seed, border),
+    };
+
+    let availability = if state.availability.now() { "available" } else { "unavailable" };
+
+    match render(state, draw).await {
+        Ok(png) => (
+            [("x-cat-availability", availability)],
+            image_response(png, Format::Png, start),
+        ).into_response(),
+        Err(response) => response,
+    }
+}
+
+/// Whether the client would rather get JSON than images, like API clients that
+/// send `Accept: application/json`.
+fn wants_json(headers: &HeaderMap) -> bool {
+    headers.get_all(ACCEPT).iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|media| media.split(';').next().unwrap_or_default().trim() == "application/json")
+}
+
+/// The body of a `400 Bad Request` for a query that couldn't be parsed.
+#[derive(Serialize)]
+struct QueryProblem {
+    error: RejectReason,
+    detail: String,
+}
+
+/// Tells an API client exactly what was wrong with its query.
+fn bad_query(error: QueryError) -> Response {
+    info!("Bad URI query for API client ({error})");
+
+    (
+        StatusCode::BAD_REQUEST,
+        Json(QueryProblem { error: RejectReason::MalformedQuery, detail: error.to_string() }),
+    ).into_response()
+}
+
+/// The query for `/torna`.
+#[derive(Deserialize)]
+struct TornaQuery {
+    /// Which message to draw. It's random if this is missing.
+    variant: Option<Variant>,
+
+    /// The seed for which message is drawn and where. It's random if this is
+    /// missing.
+    seed: Option<u64>,
+
+    /// How thick a frame is drawn around the image, in pixels. There's none
+    /// by default.
+    #[serde(default)]
+    border: u32,
+}
+
+/// When the next cat is, from `/torna` for API clients.
+#[derive(Serialize)]
+struct Torna {
+    /// Whether it's the right time anywhere.
+    available: bool,
+    /// How many seconds until the next window starts somewhere, if it isn't
+    /// available now.
+    next_cat_seconds: Option<i64>,
+}
+
+/// Tells the client to come back later. This is the placeholder image shown
+/// when it isn't the right time.
+///
+/// API clients that send `Accept: application/json` are told when to come
+/// back in JSON instead, so they don't have to look at an image.
+async fn torna(State(state): State<AppState>, Query(query): Query<TornaQuery>, headers: HeaderMap) -> Response {
+    if wants_json(&headers) {
+        let now = Utc::now();
+        let available = available_anywhere(state.config, now);
+
+        return Json(Torna {
+            available,
+            next_cat_seconds: (!available).then(|| next_window(state.config, now)).flatten().map(|(_, until)| until.num_seconds()),
+        }).into_response();
+    }
+
+    out_of_stock(&state, OutOfStock::Placeholder, query.variant, query.seed, query.border).await
+}
+
+/// Runs some drawing on a blocking thread, so the CPU-heavy tessellation and
+/// PNG encoding don't stall other requests on the async runtime (and so the
+/// request can be timed out while it's drawing).
+///
+/// Only so many images are drawn at once so a spike of requests can't hog
+/// every CPU. The rest wait their turn, unless too many are already waiting,
+/// in which case this gives up with `503 Service Unavailable`.
+pub(crate) async fn render<T: Send + 'static>(state: &AppState, draw: impl FnOnce() -> T + Send + 'static) -> Result<T, Response> {
+    let queued = &state.metrics.queued_renders;
+    if queued.fetch_add(1, Ordering::Relaxed) >= state.config.queued_renders as u64 {
+        queued.fetch_sub(1, Ordering::Relaxed);
+        warn!("Too many images waiting to be drawn");
+        return Err(StatusCode::SERVICE_UNAVAILABLE.into_response());
+    }
+
+    // The count is decremented even if the request times out while waiting
+    let permit = {
+        let _queued = Dequeue(queued);
+        state.renders.clone().acquire_owned().await.expect("the render semaphore is never closed")
+    };
+
+    tokio::task::spawn_blocking(move || {
+        let _permit = permit;
+        draw()
+    }).await.map_err(|err| {
+        error!("Drawing task failed: {err}");
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    })
+}
+
+/// Takes an image out of the render queue when dropped.
+struct Dequeue<'a>(&'a AtomicU64);
+
+impl Drop for Dequeue<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Turns an image in the given format into a response.
+/// 
+/// The time spent rendering since `start` is sent back in a `Server-Timing`
+/// header, so it shows up in the browser's devtools.
+fn image_response(image: Vec<u8>, format: Format, start: Instant) -> impl IntoResponse {
+    let timing = format!("render;dur={:.1}", start.elapsed().as_secs_f64() * 1000.);
+
+    (
+        StatusCode::OK,
+        [(CONTENT_TYPE, format.content_type())],
+        [("server-timing", timing)],
+        image
+    )
+}
+
+/// Which build of the server is running, from `/version`.
+#[derive(Serialize)]
+struct Version {
+    version: &'static str,
+    commit: &'static str,
+    hour: u32,
+    minute: u32,
+}
+
+/// Says which build is running, to check that a deploy actually rolled out.
+async fn version(State(state): State<AppState>) -> Json<Version> {
+    Json(Version {
+        version: env!("CARGO_PKG_VERSION"),
+        commit: env!("GIT_HASH"),
+        hour: state.config.hour,
+        minute: state.config.minute,
+    })
+}
+
+/// The longest range of days `/cat/archive` will list at once.
+const MAX_ARCHIVE_DAYS: i64 = 366;
+
+/// Makes the cat of the day for the given date. Like named cats, these are a
+/// showcase, so they're always available, but only once the day has come
+/// somewhere.
+async fn daily(state: &AppState, date: NaiveDate, options: CatOptions) -> Response {
+    if date > latest_day() {
+        return (StatusCode::NOT_FOUND, "that day's cat isn't here yet").into_response();
+    }
+
+    let mut response = cat(state, CatOptions { seed: Some(draw::daily_seed(date)), date: Some(date), ..options }).await;
+
+    // Name it after the day instead of the seed, keeping the extension of
+    // whatever format it actually ended up in
+    let extension = response.headers().get(CONTENT_TYPE)
+        .and_then(|kind| Format::from_content_type(kind.to_str().ok()?))
+        .map(Format::extension);
+
+    if let Some(extension) = extension {
+        if let Ok(value) = format!(r#"inline; filename="cat-{date}.{extension}""#).parse() {
+            response.headers_mut().insert(CONTENT_DISPOSITION, value);
+        }
+    }
+
+    response
+}
+
+/// The latest date it is anywhere in the world, which is UTC+14.
+fn latest_day() -> NaiveDate {
+    (Utc::now() + TimeDelta::hours(14)).date_naive()
+}
+
+/// The query for `/cat/archive`.
+#[derive(Deserialize)]
+struct ArchiveQuery {
+    from: NaiveDate,
+    to: NaiveDate,
+}
+
+/// A single day in `/cat/archive`.
+#[derive(Serialize)]
+struct ArchivedCat {
+    date: NaiveDate,
+    url: String,
+}
+
+/// Lists the cats of the day between two dates (inclusive), so past cats can
+/// be browsed.
+async fn archive(Query(query): Query<ArchiveQuery>) -> Response {
+    let to = query.to.min(latest_day());
+
+    if query.from > to {
+        return (StatusCode::BAD_REQUEST, "the range doesn't have any days with cats").into_response();
+    }
+
+    if (to - query.from).num_days() >= MAX_ARCHIVE_DAYS {
+        return (StatusCode::BAD_REQUEST, format!("the range can be at most {MAX_ARCHIVE_DAYS} days")).into_response();
+    }
+
+    let cats = query.from.iter_days()
+        .take_while(|&date| date <= to)
+        .map(|date| ArchivedCat { date, url: format!("/cat/daily/{date}") })
+        .collect::<Vec<_>>();
+
+    Json(cats).into_response()
+}
+
+/// The query for `/verify`.
+#[derive(Deserialize)]
+struct VerifyQuery {
+    /// The client's time, in milliseconds (or seconds, see `unit`).
+    time: i64,
+    /// The client's time zone offset, in minutes.
+    offset: i64,
+    /// Whether the time is in seconds or milliseconds. It's guessed from the
+    /// time if this is missing.
+    unit: Option<TimeUnit>,
+    /// The client's round-trip time to the server, in milliseconds, if it
+    /// measured one.
+    rtt: Option<i64>,
+}
+
+/// The result of a dry-run verification.
+#[derive(Serialize)]
+struct Verdict {
+    /// Whether or not the client would get a cat.
+    valid: bool,
+    /// Why the client wouldn't get a cat, if they wouldn't.
+    reason: Option<RejectReason>,
+    /// The server's current time.
+    server_time: DateTime<Utc>,
+    /// The client's local time, according to its time and offset.
+    client_time: Option<NaiveDateTime>,
+}
+
+/// Checks whether or not a time and offset would get a cat, explaining why
+/// not, without actually making one.
+/// 
+/// This doesn't go through the anticheat, so checking a time doesn't count as
+/// using it.
+async fn verify(State(state): State<AppState>, Query(query): Query<VerifyQuery>) -> Json<Verdict> {
+    let now = Utc::now();
+    let time = TimeUnit::to_millis(query.unit, query.time);
+    let result = verify_time(state.config, time, query.offset, query.rtt, now);
+
+    let client_time = DateTime::from_timestamp_millis(time)
+        .and_then(|time| time.checked_sub_signed(TimeDelta::try_minutes(query.offset)?))
+        .map(|time| time.naive_utc());
+
+    Json(Verdict {
+        valid: result.is_ok(),
+        reason: result.err(),
+        server_time: now,
+        client_time,
+    })
+}
+
+/// Whether cats can be made right now, from `/status`.
+#[derive(Serialize)]
+struct Status {
+    /// Whether it's the right time anywhere.
+    available: bool,
+    server_time: DateTime<Utc>,
+    /// How many seconds until the next window starts somewhere, if it isn't
+    /// available now.
+    next_window: Option<i64>,
+}
+
+/// Says whether it's the right time anywhere, so a client that was turned away
+/// can tell whether nobody can get a cat right now (so it should wait) or it's
+/// just them (so it should check its clock).
+async fn status(State(state): State<AppState>) -> Json<Status> {
+    let now = Utc::now();
+    let available = available_anywhere(state.config, now);
+
+    Json(Status {
+        available,
+        server_time: now,
+        next_window: (!available).then(|| next_window(state.config, now)).flatten().map(|(_, until)| until.num_seconds()),
+    })
+}
+
+/// Keeps crawlers away from everything that draws an image, since each one
+/// costs CPU and none of them are worth indexing.
+///
+/// The free cat endpoint isn't listed, since anyone can read this file and the
+/// whole point of its real URL is that nobody knows it.
+async fn robots(State(state): State<AppState>) -> impl IntoResponse {
+    let disallowed = ["/cat", "/torna", "/admin", "/ws"];
+
+    let mut robots = String::from("User-agent: *\n");
+    for path in disallowed {
+        robots += &format!("Disallow: {path}\n");
+    }
+    robots += &format!("\nSitemap: {}/sitemap.xml\n", state.config.public_url);
+
+    ([(CONTENT_TYPE, "text/plain")], robots)
+}
+
+/// The sitemap, which is just the index page.
+async fn sitemap(State(state): State<AppState>) -> impl IntoResponse {
+    let sitemap = format!(r#"<?xml version="1.0" encoding="UTF-8"?><urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9"><url><loc>{}/</loc></url></urlset>"#, state.config.public_url);
+
+    ([(CONTENT_TYPE, "application/xml")], sitemap)
+}
+
+/// Where it's the right time for cats, from `/zones`.
+#[derive(Serialize)]
+struct Zones {
+    server_time: DateTime<Utc>,
+    zones: Vec<Zone>,
+}
+
+/// A single time zone offset.
+#[derive(Serialize)]
+struct Zone {
+    /// The offset the way clients send it, in minutes (e.g. 360 for UTC-06:00).
+    offset: i64,
+    /// The offset from UTC, like `-06:00`.
+    utc: String,
+}
+
+/// Lists every time zone offset where it's the right time for cats right now,
+/// which is empty most of the time.
+async fn zones(State(state): State<AppState>) -> Json<Zones> {
+    let now = Utc::now();
+
+    let zones = valid_time_offsets().iter()
+        .filter(|&&offset| offset_allowed(state.config, offset) && valid_time_in_zone(state.config, now, offset))
+        .map(|&offset| Zone { offset, utc: format_offset(offset).trim_start_matches("UTC").into() })
+        .collect();
+
+    Json(Zones { server_time: now, zones })
+}
+
+/// Streams a new cat every [STREAM_INTERVAL] as a `multipart/x-mixed-replace`
+/// response, which browsers show as a live-updating image.
+/// 
+/// The client is checked like `GET /cat`, so it needs a `time&offset` query
+/// (and a nonce, if there's a `CAT_SECRET`), and a stream uses up the client's
+/// cat for the window if there's a [Cooldown]. Every cat in the stream counts
+/// towards the connection's limit and is taken from the [Stock], so a stream
+/// is refused when the stock is empty. The first cat is drawn before the
+/// response starts, and if it can't be, the client's cat for the window is
+/// given back. The stream ends when the window closes, after
+/// [STREAM_DURATION], when the connection or the stock runs out of cats, or
+/// when the client disconnects (which drops the stream).
+async fn cat_stream(State(state): State<AppState>, ClientIp(ip): ClientIp, ConnectInfo(connection): ConnectInfo<Connection>, RawQuery(query): RawQuery) -> Response {
+    let now = Utc::now();
+    if let Err(reason) = correct_time_for_query(state.config, state.anticheat.as_ref(), ip, query.as_deref(), now).await {
+        return out_of_stock(&state, OutOfStock::Rejected(reason), None, None, 0).await;
+    }
+
+    // The whole stream is the client's cat for this window
+    let offset = query.as_deref().and_then(parse_query).map_or(0, |(_, offset)| offset);
+    if let Some(cooldown) = &state.cooldown {
+        if !cooldown.claim(ip, offset, now) {
+            return out_of_stock(&state, OutOfStock::Rejected(RejectReason::Cooldown), None, None, 0).await;
+        }
+    }
+
+    // The first cat is drawn up front, so a stream that couldn't send one
+    // doesn't use up the client's cat
+    let first = match stream_part(&state).await {
+        Ok(part) => part,
+        Err(response) => {
+            if let Some(cooldown) = &state.cooldown {
+                cooldown.release(ip, offset);
+            }
+
+            return match response {
+                Some(response) => response,
+                None => out_of_stock(&state, OutOfStock::Rejected(RejectReason::SoldOut), None, None, 0).await,
+            };
+        }
+    };
+
+    let frames = (STREAM_DURATION.as_secs() / STREAM_INTERVAL.as_secs()) as usize;
+    let interval = tokio::time::interval_at(tokio::time::Instant::now() + STREAM_INTERVAL, STREAM_INTERVAL);
+
+    let rest = stream::unfold(interval, move |mut interval| {
+        let state = state.clone();
+        let connection = connection.clone();
+
+        async move {
+            interval.tick().await;
+
+            // The first cat was counted when the stream was asked for
+            if !state.availability.now() || !connection.count_cat(state.config) {
+                return None;
+            }
+
+            let part = stream_part(&state).await.ok()?;
+            Some((part, interval))
+        }
+    }).take(frames - 1);
+
+    let cats = stream::once(async { first }).chain(rest).map(Ok::<_, Infallible>);
+
+    (
+        StatusCode::OK,
+        [(CONTENT_TYPE, "multipart/x-mixed-replace; boundary=cat")],
+        Body::from_stream(cats),
+    ).into_response()
+}
+
+/// Draws a cat for `/cat/stream`, taking it from the [Stock], as a part of the
+/// multipart response.
+///
+/// If there's no stock left, there's no response to send instead. Otherwise,
+/// if the cat couldn't be drawn, it goes back into the stock.
+async fn stream_part(state: &AppState) -> Result<Vec<u8>, Option<Response>> {
+    if state.stock.as_ref().is_some_and(|stock| !stock.take()) {
+        return Err(None);
+    }
+
+    let seed = state.seed();
+    let config = state.config;
+    let cat = match render(state, move || draw::purchase_cat(&CatOptions::default(), seed, config)).await {
+        Ok(Ok(cat)) => Ok(cat),
+        Ok(Err(err)) => {
+            error!("Couldn't make cat for stream: {err}");
+            Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+        Err(response) => Err(response),
+    };
+
+    let cat = match cat {
+        Ok(cat) => cat,
+        Err(response) => {
+            if let Some(stock) = &state.stock {
+                stock.give_back();
+            }
+            return Err(Some(response));
+        }
+    };
+
+    state.metrics.render_duration.observe(cat.render_time);
+    state.metrics.cats.fetch_add(1, Ordering::Relaxed);
+    let png = cat.image;
+
+    let mut part = format!("--cat\r\nContent-Type: image/png\r\nContent-Length: {}\r\n\r\n", png.len()).into_bytes();
+    part.extend(png);
+    part.extend(b"\r\n");
+
+    Ok(part)
+}
\ No newline at end of file
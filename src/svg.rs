@@ -0,0 +1,172 @@
+use std::fmt::Write;
+
+use raqote::{AntialiasMode, DrawOptions, LineCap, LineJoin, Path, PathOp, Source, StrokeStyle, Transform};
+
+use crate::draw::Surface;
+
+/// A surface that writes everything drawn on it out as an SVG image, so cats
+/// can be drawn at any size without losing detail.
+///
+/// Only solid colors can be drawn, since that's all cats are drawn with.
+pub struct Svg {
+    /// The size of the image, in pixels.
+    width: i32,
+    height: i32,
+    /// The transform shapes are drawn with.
+    transform: Transform,
+    /// Every clip path pushed so far, which are only ever added to.
+    defs: String,
+    /// How many clip paths there are, for naming the next one.
+    clips: usize,
+    /// Everything drawn so far.
+    body: String,
+}
+
+impl Svg {
+    /// Makes an empty image of the given size, in pixels.
+    pub fn new(width: i32, height: i32) -> Self {
+        Self { width, height, transform: Transform::identity(), defs: String::new(), clips: 0, body: String::new() }
+    }
+
+    /// Finishes the image, returning the SVG document.
+    pub fn finish(self) -> String {
+        let (width, height) = (self.width, self.height);
+        let mut out = format!(r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#);
+
+        if !self.defs.is_empty() {
+            let _ = write!(out, "<defs>{}</defs>", self.defs);
+        }
+
+        out + &self.body + "</svg>\n"
+    }
+}
+
+impl Surface for Svg {
+    fn width(&self) -> i32 {
+        self.width
+    }
+
+    fn height(&self) -> i32 {
+        self.height
+    }
+
+    fn get_transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn set_transform(&mut self, transform: &Transform) {
+        self.transform = *transform;
+    }
+
+    fn fill(&mut self, path: &Path, src: &Source, options: &DrawOptions) {
+        let (color, opacity) = paint(src, options.alpha);
+        let attributes = format!(r#" fill="{color}"{}{}"#, fraction("fill-opacity", opacity), smoothing(options));
+
+        element(&mut self.body, path, &self.transform, &attributes);
+    }
+
+    fn stroke(&mut self, path: &Path, src: &Source, style: &StrokeStyle, options: &DrawOptions) {
+        let cap = match style.cap {
+            LineCap::Round => "round",
+            LineCap::Square => "square",
+            LineCap::Butt => "butt",
+        };
+        let join = match style.join {
+            LineJoin::Round => "round",
+            LineJoin::Miter => "miter",
+            LineJoin::Bevel => "bevel",
+        };
+
+        let (color, opacity) = paint(src, options.alpha);
+        let attributes = format!(
+            r#" fill="none" stroke="{color}"{} stroke-width="{}" stroke-linecap="{cap}" stroke-linejoin="{join}" stroke-miterlimit="{}"{}"#,
+            fraction("stroke-opacity", opacity), style.width, style.miter_limit, smoothing(options),
+        );
+
+        element(&mut self.body, path, &self.transform, &attributes);
+    }
+
+    fn push_clip(&mut self, path: &Path) {
+        let id = self.clips;
+        self.clips += 1;
+
+        let _ = write!(self.defs, r#"<clipPath id="clip{id}">"#);
+        element(&mut self.defs, path, &self.transform, "");
+        self.defs += "</clipPath>";
+
+        let _ = write!(self.body, r#"<g clip-path="url(#clip{id})">"#);
+    }
+
+    fn pop_clip(&mut self) {
+        self.body += "</g>";
+    }
+
+    fn push_layer(&mut self, opacity: f32) {
+        let _ = write!(self.body, r#"<g opacity="{opacity}">"#);
+    }
+
+    fn pop_layer(&mut self) {
+        self.body += "</g>";
+    }
+}
+
+/// Writes a path element for the given path and transform, with `attributes`
+/// on the end.
+fn element(out: &mut String, path: &Path, transform: &Transform, attributes: &str) {
+    let _ = write!(out, r#"<path d="{}""#, data(path));
+
+    if *transform != Transform::identity() {
+        let Transform { m11, m12, m21, m22, m31, m32, .. } = *transform;
+        let _ = write!(out, r#" transform="matrix({m11} {m12} {m21} {m22} {m31} {m32})""#);
+    }
+
+    let _ = write!(out, "{attributes}/>");
+}
+
+/// Writes a path as SVG path data.
+fn data(path: &Path) -> String {
+    let mut out = String::new();
+
+    for op in &path.ops {
+        let _ = match *op {
+            PathOp::MoveTo(to) => write!(out, "M{} {}", to.x, to.y),
+            PathOp::LineTo(to) => write!(out, "L{} {}", to.x, to.y),
+            PathOp::QuadTo(ctrl, to) => write!(out, "Q{} {} {} {}", ctrl.x, ctrl.y, to.x, to.y),
+            PathOp::CubicTo(ctrl1, ctrl2, to) => write!(out, "C{} {} {} {} {} {}", ctrl1.x, ctrl1.y, ctrl2.x, ctrl2.y, to.x, to.y),
+            PathOp::Close => write!(out, "Z"),
+        };
+    }
+
+    out
+}
+
+/// Returns a color as it's written in SVG, along with how opaque it is (from 0
+/// to 1). Anything but a solid color isn't drawn.
+fn paint(src: &Source, alpha: f32) -> (String, f32) {
+    let Source::Solid(color) = src else {
+        return ("none".into(), 1.);
+    };
+
+    // Solid sources are premultiplied
+    let unmultiply = |channel: u8| (channel as u32 * 0xff).checked_div(color.a as u32).unwrap_or_default().min(0xff);
+    let (r, g, b) = (unmultiply(color.r), unmultiply(color.g), unmultiply(color.b));
+
+    (format!("#{r:02x}{g:02x}{b:02x}"), color.a as f32 / 255. * alpha)
+}
+
+/// Writes an attribute for an opacity, or nothing if it's opaque.
+fn fraction(name: &str, opacity: f32) -> String {
+    match opacity < 1. {
+        true => format!(r#" {name}="{opacity}""#),
+        false => String::new(),
+    }
+}
+
+/// Writes an attribute that turns smoothing off if the draw options do, or
+/// nothing.
+fn smoothing(options: &DrawOptions) -> &'static str {
+    match options.antialias {
+        AntialiasMode::None => r#" shape-rendering="crispEdges""#,
+        AntialiasMode::Gray => "",
+    }
+}
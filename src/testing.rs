@@ -0,0 +1,37 @@
+use std::{net::{IpAddr, SocketAddr}, sync::Arc};
+
+use chrono::{DateTime, Utc};
+use tokio::net::TcpListener;
+
+use crate::{config::Config, connection::Connection, metrics::Metrics, server::{build_router, AppState}, time::{Anticheat, RejectReason}};
+
+/// An anticheat that gives everyone a cat, whatever time they say it is.
+pub struct AcceptAll;
+
+impl Anticheat for AcceptAll {
+    fn verify(&self, _: IpAddr, _: i64, _: i64, _: Option<i64>, _: DateTime<Utc>) -> Result<(), RejectReason> {
+        Ok(())
+    }
+}
+
+/// Starts a server on a random local port where every cat request gets a cat,
+/// returning its address. It runs in the background until the runtime stops,
+/// and has its own metrics, so several can run at once.
+///
+/// The server only uses the given config (like [Config::default]), never the
+/// environment. Cat requests still need a `time&offset` query (any numbers
+/// will do), and a `nonce` if the config has a secret. Ask for `seed=<seed>`
+/// to get the same cat every time.
+pub async fn spawn(config: &'static Config) -> std::io::Result<SocketAddr> {
+    let metrics = Box::leak(Box::new(Metrics::new()));
+    let state = AppState::new(config, metrics).with_anticheat(Arc::new(AcceptAll));
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let address = listener.local_addr()?;
+
+    tokio::spawn(async move {
+        axum::serve(listener, build_router(state).into_make_service_with_connect_info::<Connection>()).await
+    });
+
+    Ok(address)
+}
@@ -1,41 +1,412 @@
-use std::sync::OnceLock;
+use std::{collections::HashMap, net::IpAddr, sync::{atomic::{AtomicU64, Ordering}, Mutex, PoisonError}};
 
 use chrono::{DateTime, TimeDelta, Timelike, Utc};
-use log::{debug, info};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
 
-use crate::{CLIENT_LEEWAY, HOUR, MINUTE};
+use crate::{config::Config, nonce};
 
-/// Returns whether or not a cat should be returned for the provided URL query.
+mod offset;
+
+pub use offset::{parse_offset, EASTMOST_OFFSET, WESTMOST_OFFSET};
+
+/// Checks whether or not a cat should be returned for the provided URL query.
 /// 
 /// A valid query consists of the client's time, an ampersand (`&`), and the
-/// client's time zone offset.
+/// client's time zone offset (see [try_parse_query]). Other parameters are
+/// ignored by this function.
 /// 
 /// Client times are technically unnecessary, but prevent static URLS from
 /// working between cats, which is beneficial. Client offsets consist of any
 /// valid IANA tz database time, meaning that for most minutes it's not possible
 /// anywhere for there to be a valid time.
-pub async fn correct_time_for_query(query: Option<&str>) -> bool {
-    let parts = query.and_then(|t| t.split_once("&"))
-        .and_then(|(time, offset)| {
-            let time = time.parse::<i64>().ok()?;
-            let offset = offset.parse::<i64>().ok()?;
-
-            Some((time, offset))
-        });
+///
+/// If there's a `CAT_SECRET`, the query also needs a `nonce` parameter from a
+/// recent index page (see [crate::nonce]). Clients on slow connections can
+/// send their round-trip time in milliseconds as `rtt` (see [verify_time]).
+///
+/// The query is checked as if it were received from `ip` at `now`, so this
+/// never looks at the system clock itself.
+pub async fn correct_time_for_query(config: &Config, anticheat: &dyn Anticheat, ip: IpAddr, query: Option<&str>, now: DateTime<Utc>) -> Result<(), RejectReason> {
+    let parts = query.and_then(parse_query);
 
     let Some((time, offset)) = parts else {
         info!("Bad URI query {}", query.map(|q| format!("'{q}'")).unwrap_or("N/A".into()));
-        return false;
+        return Err(RejectReason::MalformedQuery);
     };
 
-    if !verify_time(time, offset).is_some() {
-        info!("Bad time {time} and offset {offset}");
-        return false;
+    let given = query.and_then(|query| query.split('&').find_map(|part| part.strip_prefix("nonce=")));
+    let rtt = query.and_then(|query| query.split('&').find_map(|part| part.strip_prefix("rtt=")?.parse().ok()));
+
+    correct_time(config, anticheat, ip, time, offset, rtt, given, now)
+}
+
+/// Like [correct_time_for_query], but for a time (in milliseconds), offset,
+/// round-trip time, and nonce that were already taken out of a request, like
+/// from a JSON body.
+#[allow(clippy::too_many_arguments)]
+pub fn correct_time(config: &Config, anticheat: &dyn Anticheat, ip: IpAddr, time: i64, offset: i64, rtt: Option<i64>, given: Option<&str>, now: DateTime<Utc>) -> Result<(), RejectReason> {
+    if !nonce::check(config, given, now) {
+        info!("Bad nonce {}", given.unwrap_or("N/A"));
+        return Err(RejectReason::BadNonce);
+    }
+
+    if let Err(reason) = anticheat.verify(ip, time, offset, rtt, now) {
+        info!("Bad time {time} and offset {offset} ({reason})");
+        return Err(reason);
     }
 
     info!("Good time {time} and offset {offset}");
     
-    true
+    Ok(())
+}
+
+/// The reason a client was refused a cat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectReason {
+    /// The query didn't have a time and offset.
+    MalformedQuery,
+    /// The client's offset isn't in the IANA tz database.
+    UnknownOffset,
+    /// It isn't the correct time in the client's time zone.
+    WrongTime,
+    /// The client's clock is too far behind the server's.
+    ClockBehind,
+    /// The client's clock is too far ahead of the server's.
+    ClockAhead,
+    /// The client doesn't think it's the correct time.
+    ClientWrongTime,
+    /// The client's time couldn't be represented as a date.
+    InvalidTime,
+    /// The same client already got a cat with the exact same time and offset.
+    Replay,
+    /// The nonce was missing, made up, or too old.
+    BadNonce,
+    /// The client's offset is real, but this instance doesn't give cats to it.
+    OffsetNotAllowed,
+    /// The client already got a cat this window (see [Cooldown]).
+    Cooldown,
+    /// Every cat for this window is gone (see [Stock]).
+    SoldOut,
+}
+
+impl RejectReason {
+    /// Every reason, in order.
+    pub const ALL: [RejectReason; 12] = [
+        RejectReason::MalformedQuery,
+        RejectReason::UnknownOffset,
+        RejectReason::WrongTime,
+        RejectReason::ClockBehind,
+        RejectReason::ClockAhead,
+        RejectReason::ClientWrongTime,
+        RejectReason::InvalidTime,
+        RejectReason::Replay,
+        RejectReason::BadNonce,
+        RejectReason::OffsetNotAllowed,
+        RejectReason::Cooldown,
+        RejectReason::SoldOut,
+    ];
+
+    /// Whether the request was well-formed but fishy, like a made-up time,
+    /// rather than a mistake or a cat that isn't available here.
+    ///
+    /// Asking when the window has just closed isn't suspicious, since honest
+    /// clients that lag a few seconds behind do it all the time. Neither are
+    /// replays, since clients behind the same address that send times in
+    /// seconds can honestly send the same one.
+    pub fn suspicious(self) -> bool {
+        matches!(self, RejectReason::ClientWrongTime | RejectReason::ClockAhead)
+    }
+}
+
+impl std::fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            RejectReason::MalformedQuery => "malformed_query",
+            RejectReason::UnknownOffset => "unknown_offset",
+            RejectReason::WrongTime => "wrong_time",
+            RejectReason::ClockBehind => "clock_behind",
+            RejectReason::ClockAhead => "clock_ahead",
+            RejectReason::ClientWrongTime => "client_wrong_time",
+            RejectReason::InvalidTime => "invalid_time",
+            RejectReason::Replay => "replay",
+            RejectReason::BadNonce => "bad_nonce",
+            RejectReason::OffsetNotAllowed => "offset_not_allowed",
+            RejectReason::Cooldown => "cooldown",
+            RejectReason::SoldOut => "sold_out",
+        })
+    }
+}
+
+/// Parses the client's time (in milliseconds) and offset from a `time&offset`
+/// query, ignoring any other parameters except for `unit` (see [TimeUnit]) and
+/// `offsetunit` (see [OffsetUnit]).
+pub fn parse_query(query: &str) -> Option<(i64, i64)> {
+    try_parse_query(query).ok()
+}
+
+/// Like [parse_query], but says what was wrong with the query.
+///
+/// The time and offset are the first two parts without a `=`, so named
+/// parameters can go anywhere (even before them) and empty parts from stray
+/// `&`s are skipped. They can also be named, like `time=...&offset=...`, which
+/// takes priority.
+pub fn try_parse_query(query: &str) -> Result<(i64, i64), QueryError> {
+    let parts = query.split('&').map(str::trim).filter(|part| !part.is_empty());
+    let named = |name: &str| parts.clone().find_map(|part| part.strip_prefix(name)?.strip_prefix('='));
+
+    let mut positional = parts.clone().filter(|part| !part.contains('='));
+    let (time, offset) = (positional.next(), positional.next());
+
+    let time = named("time").or(time).unwrap_or_default();
+    let time = time.parse::<i64>().map_err(|_| QueryError::InvalidTime(time.into()))?;
+
+    let offset = named("offset").or(offset).ok_or(QueryError::MissingOffset)?;
+    let offset_unit = named("offsetunit")
+        .map(|unit| unit.parse::<OffsetUnit>().map_err(|_| QueryError::InvalidOffsetUnit(unit.into())))
+        .transpose()?;
+    let offset = offset.parse::<i64>().ok()
+        .and_then(|offset| OffsetUnit::to_minutes(offset_unit, offset))
+        .ok_or_else(|| QueryError::InvalidOffset(offset.into()))?;
+
+    let unit = named("unit")
+        .map(|unit| unit.parse::<TimeUnit>().map_err(|_| QueryError::InvalidUnit(unit.into())))
+        .transpose()?;
+
+    Ok((TimeUnit::to_millis(unit, time), offset))
+}
+
+/// The unit of a client's time. Browsers send milliseconds, but it's easy to
+/// send seconds from something like `date +%s`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum TimeUnit {
+    #[serde(rename = "s")]
+    Seconds,
+    #[serde(rename = "ms")]
+    Milliseconds,
+}
+
+impl TimeUnit {
+    /// Times smaller than this are guessed to be in seconds, since in
+    /// milliseconds they'd be from before 1973 (and in seconds they'd be from
+    /// after the year 5000).
+    const SECONDS_BELOW: i64 = 100_000_000_000;
+
+    /// Converts a time in the given unit to milliseconds, guessing the unit
+    /// from how big the time is if there isn't one.
+    pub fn to_millis(unit: Option<TimeUnit>, time: i64) -> i64 {
+        let unit = unit.unwrap_or(if time.abs() < Self::SECONDS_BELOW { TimeUnit::Seconds } else { TimeUnit::Milliseconds });
+
+        match unit {
+            TimeUnit::Seconds => time.saturating_mul(1000),
+            TimeUnit::Milliseconds => time,
+        }
+    }
+}
+
+impl std::str::FromStr for TimeUnit {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "s" => Ok(TimeUnit::Seconds),
+            "ms" => Ok(TimeUnit::Milliseconds),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The unit of a client's offset. Browsers send minutes west of UTC (from
+/// `getTimezoneOffset()`), but POSIX clients have seconds east of UTC (from
+/// `tm_gmtoff`), like `19800` for UTC+05:30.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OffsetUnit {
+    Minutes,
+    Seconds,
+}
+
+impl OffsetUnit {
+    /// Converts an offset in the given unit (minutes if there isn't one) to
+    /// the way browsers send offsets, returning `None` for offsets in seconds
+    /// that aren't a whole number of minutes.
+    pub fn to_minutes(unit: Option<OffsetUnit>, offset: i64) -> Option<i64> {
+        match unit.unwrap_or(OffsetUnit::Minutes) {
+            OffsetUnit::Minutes => Some(offset),
+            // Seconds go the other way, since they're east of UTC
+            OffsetUnit::Seconds => (offset % 60 == 0).then(|| -offset / 60),
+        }
+    }
+}
+
+impl std::str::FromStr for OffsetUnit {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "m" => Ok(OffsetUnit::Minutes),
+            "s" => Ok(OffsetUnit::Seconds),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Why a `time&offset` query couldn't be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryError {
+    /// There was no query at all.
+    Missing,
+    /// There was no `&` between the time and the offset.
+    MissingOffset,
+    /// The time wasn't a whole number of milliseconds.
+    InvalidTime(String),
+    /// The offset wasn't a whole number of minutes.
+    InvalidOffset(String),
+    /// The unit wasn't `s` or `ms`.
+    InvalidUnit(String),
+    /// The offset unit wasn't `m` or `s`.
+    InvalidOffsetUnit(String),
+}
+
+impl std::fmt::Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueryError::Missing => write!(f, "expected a query like ?time&offset"),
+            QueryError::MissingOffset => write!(f, "expected an offset after the time, separated by '&'"),
+            QueryError::InvalidTime(time) => write!(f, "time '{time}' isn't a whole number of milliseconds or seconds"),
+            QueryError::InvalidOffset(offset) => write!(f, "offset '{offset}' isn't a whole number of minutes"),
+            QueryError::InvalidUnit(unit) => write!(f, "unit '{unit}' isn't 's' or 'ms'"),
+            QueryError::InvalidOffsetUnit(unit) => write!(f, "offset unit '{unit}' isn't 'm' or 's'"),
+        }
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// Decides whether or not a client gets a cat.
+pub trait Anticheat: Send + Sync {
+    /// Verifies that a client at `ip` with the given time (in milliseconds),
+    /// time zone offset (in minutes), and round-trip time (in milliseconds, if
+    /// it sent one) should get a cat at `now`.
+    fn verify(&self, ip: IpAddr, time: i64, offset: i64, rtt: Option<i64>, now: DateTime<Utc>) -> Result<(), RejectReason>;
+}
+
+/// The default anticheat, which checks clients with [verify_time] and rejects
+/// exact replays of a time and offset that already got the same client a cat.
+/// Times in seconds are the same for everyone in an offset who asks in the
+/// same second, so they're only replays from the same address.
+pub struct StandardAnticheat {
+    /// The settings clients are checked against.
+    config: &'static Config,
+    /// How long a time that got a cat is remembered for, which is as long as
+    /// the drift checks would accept it.
+    memory: TimeDelta,
+    /// The clients, times, and offsets that recently got a cat, along with
+    /// when.
+    seen: Mutex<HashMap<(IpAddr, i64, i64), DateTime<Utc>>>,
+}
+
+impl StandardAnticheat {
+    /// Makes an anticheat that checks clients against the given config.
+    pub fn new(config: &'static Config) -> Self {
+        // A time is first accepted when the server is as far behind it as
+        // clocks can be ahead, and last when it's lagging as much as it can
+        let memory = config.max_clock_ahead.saturating_add(config.max_clock_behind).saturating_add(config.max_rtt_allowance);
+        let memory = TimeDelta::try_milliseconds(memory).unwrap_or(TimeDelta::max_value());
+
+        Self { config, memory, seen: Mutex::default() }
+    }
+}
+
+impl Anticheat for StandardAnticheat {
+    fn verify(&self, ip: IpAddr, time: i64, offset: i64, rtt: Option<i64>, now: DateTime<Utc>) -> Result<(), RejectReason> {
+        verify_time(self.config, time, offset, rtt, now)?;
+
+        let mut seen = self.seen.lock().unwrap_or_else(PoisonError::into_inner);
+
+        // Forget anything too old to pass the drift checks anyway
+        seen.retain(|_, &mut at| now - at <= self.memory);
+
+        if seen.insert((ip, time, offset), now).is_some() {
+            warn!("Replayed time {time} and offset {offset} from {ip}");
+            return Err(RejectReason::Replay);
+        }
+
+        Ok(())
+    }
+}
+
+/// How long a client that got a cat is remembered for by [Cooldown]. This
+/// should be longer than a window lasts (with leeway), but much shorter than
+/// the 12 hours until the same offset's next window.
+const COOLDOWN: TimeDelta = TimeDelta::minutes(5);
+
+/// Remembers which clients got a cat in each offset's window, so each one only
+/// gets a single cat per window. Turned on with `CAT_ONE_PER_WINDOW`.
+#[derive(Default)]
+pub struct Cooldown {
+    /// The clients and offsets that recently got a cat, along with when.
+    claimed: Mutex<HashMap<(IpAddr, i64), DateTime<Utc>>>,
+}
+
+impl Cooldown {
+    /// Claims the cat for a client in the window for `offset`, returning
+    /// whether or not it's the first one.
+    pub fn claim(&self, ip: IpAddr, offset: i64, now: DateTime<Utc>) -> bool {
+        let mut claimed = self.claimed.lock().unwrap_or_else(PoisonError::into_inner);
+
+        // Forget anything from windows that are over
+        claimed.retain(|_, &mut at| now - at < COOLDOWN);
+
+        if claimed.contains_key(&(ip, offset)) {
+            info!("{ip} already got a cat in offset {offset}");
+            return false;
+        }
+
+        claimed.insert((ip, offset), now);
+        true
+    }
+
+    /// Gives back a claim, like if the cat couldn't be made after all.
+    pub fn release(&self, ip: IpAddr, offset: i64) {
+        self.claimed.lock().unwrap_or_else(PoisonError::into_inner).remove(&(ip, offset));
+    }
+}
+
+/// How many cats are left for the current window, when there's only so many
+/// per window. Set up with `CAT_STOCK_PER_WINDOW`.
+pub struct Stock {
+    /// How many cats each window starts with.
+    per_window: u64,
+    /// How many cats are left.
+    left: AtomicU64,
+}
+
+impl Stock {
+    /// Makes a full stock of `per_window` cats.
+    pub fn new(per_window: u64) -> Self {
+        Self { per_window, left: AtomicU64::new(per_window) }
+    }
+
+    /// Takes a cat from the stock, returning whether or not there was one.
+    pub fn take(&self) -> bool {
+        self.left.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |left| left.checked_sub(1)).is_ok()
+    }
+
+    /// Puts a cat back, like if it couldn't be made after all.
+    pub fn give_back(&self) {
+        let _ = self.left.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |left| (left < self.per_window).then_some(left + 1));
+    }
+
+    /// Fills the stock back up for a new window.
+    pub fn refill(&self) {
+        self.left.store(self.per_window, Ordering::Relaxed);
+    }
+
+    /// How many cats are left.
+    pub fn left(&self) -> u64 {
+        self.left.load(Ordering::Relaxed)
+    }
 }
 
 /// Verifies that the client time and offset are valid. This will perform a few
@@ -43,99 +414,499 @@ pub async fn correct_time_for_query(query: Option<&str>) -> bool {
 /// - The client must have a valid time zone offset according to the IANA tz
 ///   database
 /// - It must be the correct time in the client's time zone (except for a small
-///   [CLIENT_LEEWAY]).
+///   [Config::client_leeway_before] and [Config::client_leeway_after]).
 /// 
 /// There are a few more checks that are technically unnecessary for the
 /// anticheat, but render static URLs useless and make it slightly harder to
 /// reverse engineer:
-/// - The client's time cannot have more than 15 seconds of drift from the
-///   actual time
+/// - The client's time cannot be more than [Config::max_clock_behind]
+///   milliseconds behind or [Config::max_clock_ahead] milliseconds ahead of
+///   the actual time. If
+///   there's a `CAT_MAX_RTT_ALLOWANCE_MS`, clients that send their round-trip
+///   time (`rtt`) can be behind by half of it more, up to that cap.
 /// - The client's time, taking offset into account, must actually be the
 ///   correct time for them (no leeway here, because this is what the client
 ///   thinks).
-pub fn verify_time(time: i64, offset: i64) -> Option<()> {
-
-    let now = Utc::now();
+///
+/// The server's time is `now` rather than the system clock, so any instant can
+/// be checked.
+pub fn verify_time(config: &Config, time: i64, offset: i64, rtt: Option<i64>, now: DateTime<Utc>) -> Result<(), RejectReason> {
 
     // The client must have an offset that corresponds to a valid time zone
     if !valid_time_offsets().contains(&offset) {
         debug!("Offset {offset} not in IANA time zone database");
-        return None;
+        return Err(RejectReason::UnknownOffset);
+    }
+
+    // ...that this instance gives cats to
+    if !offset_allowed(config, offset) {
+        debug!("Offset {offset} isn't allowed here");
+        return Err(RejectReason::OffsetNotAllowed);
     }
 
     // Make sure the local time is actually valid
-    if !valid_time_in_zone(now, offset) {
-        debug!("Not {HOUR}:{MINUTE:0>2} in time offset {offset}");
-        return None;
+    if !valid_time_in_zone(config, now, offset) {
+        debug!("Not {}:{:0>2} in time offset {offset}", config.hour, config.minute);
+        return Err(RejectReason::WrongTime);
     }
 
     // Client time checks
 
-    // The client cannot be too desynced (here we chose 15s)
-    if now.timestamp_millis().abs_diff(time) > 15_000 {
-        debug!("Client system time {time} drifts too much ({}ms > 15000ms)", now.timestamp_millis().abs_diff(time));
-        return None;
+    // The client cannot be too desynced. Clocks that lag are common, but a
+    // client that's ahead might be asking for a 2:22 that hasn't happened yet,
+    // so the two directions are checked separately.
+    let drift = now.timestamp_millis().checked_sub(time).ok_or(RejectReason::InvalidTime)?;
+    let max_behind = config.max_clock_behind + rtt_allowance(config, rtt);
+    if drift > max_behind {
+        debug!("Client system time {time} lags too much ({drift}ms > {max_behind}ms)");
+        return Err(RejectReason::ClockBehind);
+    }
+    if -drift > config.max_clock_ahead {
+        warn!("Client system time {time} is too far ahead ({}ms > {}ms)", -drift, config.max_clock_ahead);
+        return Err(RejectReason::ClockAhead);
     }
 
     // Client must think it's actually the correct time
-    let time = DateTime::from_timestamp_millis(time)?.checked_sub_signed(TimeDelta::minutes(offset))?;
-    if time.hour12().1 != HOUR || time.minute() != MINUTE {
-        debug!("Client thinks it's {}:{:0>2} instead of {HOUR}:{MINUTE:0>2}", time.hour12().1, time.minute());
-        return None;
+    let time = DateTime::from_timestamp_millis(time)
+        .and_then(|time| time.checked_sub_signed(TimeDelta::minutes(offset)))
+        .ok_or(RejectReason::InvalidTime)?;
+    if time.hour() % 12 != config.hour % 12 || time.minute() != config.minute {
+        debug!("Client thinks it's {}:{:0>2} instead of {}:{:0>2}", time.hour12().1, time.minute(), config.hour, config.minute);
+        return Err(RejectReason::ClientWrongTime);
     }
 
     // Must be good!
-    Some(())
+    Ok(())
+}
+
+/// How much of a client's round-trip time its clock can lag by on top of
+/// [Config::max_clock_behind], since a request takes about half of it to arrive.
+const RTT_ALLOWANCE_FRACTION: f64 = 0.5;
+
+/// How many more milliseconds a client's clock can lag by because of its
+/// round-trip time, which is none unless there's a `CAT_MAX_RTT_ALLOWANCE_MS`.
+/// Clients pick their own round-trip time, so the cap is all that keeps this
+/// from being abused.
+fn rtt_allowance(config: &Config, rtt: Option<i64>) -> i64 {
+    let rtt = rtt.unwrap_or_default().max(0);
+
+    ((rtt as f64 * RTT_ALLOWANCE_FRACTION) as i64).min(config.max_rtt_allowance)
+}
+
+/// Returns whether or not the provided date has the correct [Config::hour]
+/// and [Config::minute] in the given time zone offset. Both the hour and the
+/// hour twelve hours later (wrapping around midnight) are checked. This will
+/// allow a leeway of [Config::client_leeway_before] before the minute starts
+/// and [Config::client_leeway_after] after it ends, even if that crosses
+/// midnight.
+///
+/// Offsets are fixed, so unlike with named time zones, every local time exists
+/// exactly once and there's no daylight saving gap to fall into. Clients that
+/// just sprang forward send their new offset, which is checked like any other.
+/// The only thing that can go wrong is an offset so big that the local time
+/// can't be represented, which is never valid.
+pub fn valid_time_in_zone(config: &Config, now: DateTime<Utc>, offset: i64) -> bool {
+    const DAY: i64 = 24 * 60 * 60 * 1000;
+
+    let Some(time) = TimeDelta::try_minutes(offset).and_then(|offset| now.checked_sub_signed(offset)) else {
+        debug!("Offset {offset} can't be applied to {now}");
+        return false;
+    };
+
+    // Work in milliseconds into the day, so the window can wrap around midnight
+    let elapsed = time.num_seconds_from_midnight() as i64 * 1000 + time.timestamp_subsec_millis() as i64;
+
+    [config.hour, (config.hour + 12) % 24].into_iter().any(|hour| {
+        let middle = ((hour * 60 + config.minute) as i64 * 60 + 30) * 1000;
+
+        // How far past the middle of the minute it is, so early is negative
+        let delta = (elapsed - middle + DAY / 2).rem_euclid(DAY) - DAY / 2;
+
+        (-(30 + config.client_leeway_before) * 1000..=(30 + config.client_leeway_after) * 1000).contains(&delta)
+    })
+}
+
+/// Returns whether or not it's the correct time in any valid (and allowed)
+/// time zone.
+pub fn available_anywhere(config: &Config, now: DateTime<Utc>) -> bool {
+    current_window(config, now).is_some()
 }
 
-/// Returns whether or not the provided date has the correct [HOUR] and [MINUTE]
-/// in the given time zone offset. This will allow a leeway of [CLIENT_LEEWAY]
-/// in either direction.
+/// Returns the first valid (and allowed) offset where it's the correct time,
+/// if there is one.
+pub fn current_window(config: &Config, now: DateTime<Utc>) -> Option<i64> {
+    valid_time_offsets().iter().copied().find(|&offset| offset_allowed(config, offset) && valid_time_in_zone(config, now, offset))
+}
+
+/// Returns the offset that will next have the correct [Config::hour] and
+/// [Config::minute], along with how long it is until then.
 /// 
-/// Failure of operations involving time is considered an invalid date and will
-/// return false.
-pub fn valid_time_in_zone(now: DateTime<Utc>, offset: i64) -> bool {
-    (|| {
-        let offset = TimeDelta::try_minutes(offset)?;
-        let time = now.checked_sub_signed(offset)?;
-
-        let delta = TimeDelta::min(
-            (time.with_hour(HOUR)?.with_minute(MINUTE)?.with_second(30)? - time).abs(),
-            (time.with_hour(12 + HOUR)?.with_minute(MINUTE)?.with_second(30)? - time).abs(),
-        );
-
-        if delta <= TimeDelta::try_seconds(30 + CLIENT_LEEWAY)? {
-            Some(())
-        } else {
-            None
+/// Offsets where it's currently the correct time are not considered, since
+/// their window has already started.
+pub fn next_window(config: &Config, now: DateTime<Utc>) -> Option<(i64, TimeDelta)> {
+    // The window happens twice a day, so only the time into the current half
+    // of the day matters.
+    const HALF_DAY: i64 = 12 * 60 * 60;
+    let target = ((config.hour % 12) * 60 + config.minute) as i64 * 60;
+
+    valid_time_offsets().iter().filter(|&&offset| offset_allowed(config, offset)).filter_map(|&offset| {
+        let time = now.checked_sub_signed(TimeDelta::try_minutes(offset)?)?;
+        let elapsed = time.num_seconds_from_midnight() as i64 % HALF_DAY;
+
+        match (target - elapsed).rem_euclid(HALF_DAY) {
+            0 => None,
+            until => Some((offset, TimeDelta::try_seconds(until)?)),
         }
-    })().is_some()
+    }).min_by_key(|&(_, until)| until)
+}
+
+/// Formats a time zone offset (as used by clients, e.g. 360 for UTC-06:00)
+/// like `UTC-06:00`.
+pub fn format_offset(offset: i64) -> String {
+    let sign = if offset > 0 { '-' } else { '+' };
+    let offset = offset.abs();
+
+    format!("UTC{sign}{:0>2}:{:0>2}", offset / 60, offset % 60)
 }
 
 /// Returns the list of every valid time zone offset, per the time zone list.
-/// This will panic on most errors because it's meant to run once and is not
-/// some core function that requires incredible reliability.
-pub fn valid_time_offsets() -> &'static Vec<i64> {
-    static OFFSETS: OnceLock<Vec<i64>> = OnceLock::new();
-    OFFSETS.get_or_init(|| {
-        let zones = include_str!("../time-zones.txt");
-
-        zones.lines().map(|line| {
-            let (sign, line) = line.split_at(1);
-            let (hour, minute) = line.split_once(":").unwrap();
-
-            let sign = match sign {
-                "+" => 1,
-                "-" => -1,
-                c => panic!("Found invalid sign {c} while parsing line"),
-            };
-
-            let hour = hour.parse::<i64>().unwrap();
-            let minute = minute.parse::<i64>().unwrap();
-
-            // Multiply -1 because offsets are negated;
-            // e.g. offset for UTC-06:00 is 360.
-            -1 * sign * (hour * 60 + minute)
-        }).collect::<Vec<_>>()
-    })
-}
\ No newline at end of file
+/// The list is checked and turned into offsets by the build script, so it
+/// can't be missing or broken by the time the server runs.
+pub fn valid_time_offsets() -> &'static [i64] {
+    const OFFSETS: &[i64] = &include!(concat!(env!("OUT_DIR"), "/time_zones.rs"));
+    OFFSETS
+}
+
+/// Returns whether or not this instance gives cats to the given offset, per
+/// `CAT_ALLOWED_OFFSETS` and `CAT_BLOCKED_OFFSETS`. Every offset is allowed by
+/// default.
+pub fn offset_allowed(config: &Config, offset: i64) -> bool {
+    config.allowed_offsets.as_ref().is_none_or(|allowed| allowed.contains(&offset))
+        && !config.blocked_offsets.contains(&offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses a UTC time like `2024-01-01T02:22:30Z`.
+    fn at(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    #[test]
+    fn allowed_and_blocked_offsets() {
+        let config = Config::default();
+        assert!(offset_allowed(&config, -330));
+        assert!(offset_allowed(&config, 360));
+
+        let config = Config { allowed_offsets: Some(vec![-330, 360]), ..Config::default() };
+        assert!(offset_allowed(&config, -330));
+        assert!(offset_allowed(&config, 360));
+        assert!(!offset_allowed(&config, 0));
+
+        let config = Config { blocked_offsets: vec![300], ..Config::default() };
+        assert!(!offset_allowed(&config, 300));
+        assert!(offset_allowed(&config, 240));
+
+        // Blocking wins over allowing
+        let config = Config { allowed_offsets: Some(vec![300, 240]), blocked_offsets: vec![300], ..Config::default() };
+        assert!(!offset_allowed(&config, 300));
+        assert!(offset_allowed(&config, 240));
+    }
+
+    #[test]
+    fn windows_when_the_hour_is_twelve() {
+        let config = Config { hour: 12, ..Config::default() };
+
+        // 12 and 0 are the same hour on a 12 hour clock
+        assert!(valid_time_in_zone(&config, at("2024-01-01T12:22:30Z"), 0));
+        assert!(valid_time_in_zone(&config, at("2024-01-01T00:22:30Z"), 0));
+        assert!(!valid_time_in_zone(&config, at("2024-01-01T02:22:30Z"), 0));
+        assert!(!valid_time_in_zone(&config, at("2024-01-01T11:22:30Z"), 0));
+
+        let now = at("2024-01-01T00:22:30Z");
+        assert_eq!(verify_time(&config, now.timestamp_millis(), 0, None, now), Ok(()));
+        let now = at("2024-01-01T12:22:30Z");
+        assert_eq!(verify_time(&config, now.timestamp_millis(), 0, None, now), Ok(()));
+    }
+
+    #[test]
+    fn verify_time_reasons() {
+        let config = Config::default();
+        let now = at("2024-01-01T02:22:30Z");
+        let time = now.timestamp_millis();
+
+        assert_eq!(verify_time(&config, time, 0, None, now), Ok(()));
+        assert_eq!(verify_time(&config, time, 7, None, now), Err(RejectReason::UnknownOffset));
+        assert_eq!(verify_time(&config, time, 60, None, now), Err(RejectReason::WrongTime));
+        assert_eq!(verify_time(&config, time - 16_000, 0, None, now), Err(RejectReason::ClockBehind));
+        assert_eq!(verify_time(&config, time + 6_000, 0, None, now), Err(RejectReason::ClockAhead));
+        assert_eq!(verify_time(&config, i64::MIN, 0, None, now), Err(RejectReason::InvalidTime));
+
+        let blocked = Config { blocked_offsets: vec![0], ..Config::default() };
+        assert_eq!(verify_time(&blocked, time, 0, None, now), Err(RejectReason::OffsetNotAllowed));
+
+        // Close enough to the server, but the client thinks it's 2:21
+        let now = at("2024-01-01T02:22:05Z");
+        let time = now.timestamp_millis() - 10_000;
+        assert_eq!(verify_time(&config, time, 0, None, now), Err(RejectReason::ClientWrongTime));
+
+        // Just past the end of the minute is still fine for the server, but
+        // not for a client that thinks it's 2:23
+        let now = at("2024-01-01T02:23:00.500Z");
+        assert_eq!(verify_time(&config, now.timestamp_millis() - 1_000, 0, None, now), Ok(()));
+        assert_eq!(verify_time(&config, now.timestamp_millis(), 0, None, now), Err(RejectReason::ClientWrongTime));
+        assert!(!valid_time_in_zone(&config, at("2024-01-01T02:23:01.500Z"), 0));
+    }
+
+    #[test]
+    fn rtt_lets_clocks_lag_more() {
+        let config = Config { max_rtt_allowance: 10_000, ..Config::default() };
+        let now = at("2024-01-01T02:22:50Z");
+        let time = now.timestamp_millis() - 20_000;
+
+        assert_eq!(verify_time(&config, time, 0, None, now), Err(RejectReason::ClockBehind));
+        assert_eq!(verify_time(&config, time, 0, Some(8_000), now), Err(RejectReason::ClockBehind));
+        assert_eq!(verify_time(&config, time, 0, Some(20_000), now), Ok(()));
+
+        // The allowance is capped
+        assert_eq!(verify_time(&config, time - 6_000, 0, Some(1_000_000), now), Err(RejectReason::ClockBehind));
+    }
+
+    #[tokio::test]
+    async fn correct_time_reasons() {
+        let config: &'static Config = Box::leak(Box::default());
+        let anticheat = StandardAnticheat::new(config);
+        let ip = IpAddr::from([127, 0, 0, 1]);
+        let now = at("2024-01-01T02:22:30Z");
+        let time = now.timestamp_millis();
+
+        assert_eq!(correct_time_for_query(config, &anticheat, ip, None, now).await, Err(RejectReason::MalformedQuery));
+        assert_eq!(correct_time_for_query(config, &anticheat, ip, Some("nope"), now).await, Err(RejectReason::MalformedQuery));
+        assert_eq!(correct_time_for_query(config, &anticheat, ip, Some(&format!("{time}&0")), now).await, Ok(()));
+        assert_eq!(correct_time_for_query(config, &anticheat, ip, Some(&format!("{time}&0")), now).await, Err(RejectReason::Replay));
+
+        // A replay is forgotten once it couldn't pass the drift checks anyway
+        let later = now + anticheat.memory + TimeDelta::milliseconds(1);
+        assert_eq!(anticheat.verify(ip, time, 0, None, later), Err(RejectReason::ClockBehind));
+
+        let config: &'static Config = Box::leak(Box::new(Config { secret: Some("secret".into()), ..Config::default() }));
+        let anticheat = StandardAnticheat::new(config);
+        let nonce = nonce::issue(config, now).unwrap();
+
+        assert_eq!(correct_time(config, &anticheat, ip, time, 0, None, None, now), Err(RejectReason::BadNonce));
+        assert_eq!(correct_time(config, &anticheat, ip, time, 0, None, Some("made-up"), now), Err(RejectReason::BadNonce));
+        assert_eq!(correct_time(config, &anticheat, ip, time, 0, None, Some(&nonce), now), Ok(()));
+    }
+
+    #[test]
+    fn replays_are_remembered_while_they_would_pass() {
+        let config: &'static Config = Box::leak(Box::new(Config { max_clock_behind: 60_000, max_rtt_allowance: 5_000, ..Config::default() }));
+        let anticheat = StandardAnticheat::new(config);
+        let ip = IpAddr::from([127, 0, 0, 1]);
+        let now = at("2024-01-01T02:22:00Z");
+        let time = now.timestamp_millis();
+
+        assert_eq!(anticheat.verify(ip, time, 0, None, now), Ok(()));
+
+        // Lagging clocks are still accepted long after the time was first used
+        let later = now + TimeDelta::seconds(59);
+        assert_eq!(anticheat.verify(ip, time, 0, Some(10_000), later), Err(RejectReason::Replay));
+    }
+
+    #[test]
+    fn same_second_from_two_clients() {
+        let config: &'static Config = Box::leak(Box::default());
+        let anticheat = StandardAnticheat::new(config);
+        let (first, second) = (IpAddr::from([192, 0, 2, 1]), IpAddr::from([192, 0, 2, 2]));
+        let now = at("2024-01-01T02:22:30.400Z");
+        let time = TimeUnit::to_millis(Some(TimeUnit::Seconds), now.timestamp());
+
+        // Everyone who asks in the same second sends the same time
+        assert_eq!(anticheat.verify(first, time, 0, None, now), Ok(()));
+        assert_eq!(anticheat.verify(second, time, 0, None, now), Ok(()));
+        assert_eq!(anticheat.verify(first, time, 0, None, now), Err(RejectReason::Replay));
+        assert!(!RejectReason::Replay.suspicious());
+    }
+
+    #[test]
+    fn cooldown_and_stock() {
+        let cooldown = Cooldown::default();
+        let ip = IpAddr::from([127, 0, 0, 1]);
+        let now = at("2024-01-01T02:22:30Z");
+
+        assert!(cooldown.claim(ip, 0, now));
+        assert!(!cooldown.claim(ip, 0, now));
+        assert!(cooldown.claim(ip, -60, now));
+        assert!(cooldown.claim(IpAddr::from([127, 0, 0, 2]), 0, now));
+        assert!(cooldown.claim(ip, 0, now + COOLDOWN));
+
+        cooldown.release(ip, -60);
+        assert!(cooldown.claim(ip, -60, now));
+
+        let stock = Stock::new(2);
+        assert!(stock.take());
+        assert!(stock.take());
+        assert!(!stock.take());
+        assert_eq!(stock.left(), 0);
+
+        stock.give_back();
+        stock.give_back();
+        stock.give_back();
+        assert_eq!(stock.left(), 2);
+
+        assert!(stock.take());
+        stock.refill();
+        assert_eq!(stock.left(), 2);
+    }
+
+    #[test]
+    fn windows_around_daylight_saving() {
+        let config = Config::default();
+
+        // New York springs forward from UTC-05:00 to UTC-04:00 at 07:00 UTC,
+        // so its 2:22 only happens in the new offset
+        let spring = at("2024-03-10T06:22:30Z");
+        assert!(valid_time_in_zone(&config, spring, 240));
+        assert!(!valid_time_in_zone(&config, spring, 300));
+        let spring = at("2024-03-10T07:22:30Z");
+        assert!(valid_time_in_zone(&config, spring, 300));
+        assert!(!valid_time_in_zone(&config, spring, 240));
+
+        // ...and falls back at 06:00 UTC, before its 2:22 in the old offset
+        assert!(!valid_time_in_zone(&config, at("2024-11-03T06:22:30Z"), 300));
+        assert!(valid_time_in_zone(&config, at("2024-11-03T07:22:30Z"), 300));
+
+        // London springs forward from UTC+00:00 to UTC+01:00 at 01:00 UTC
+        let spring = at("2024-03-31T01:22:30Z");
+        assert!(valid_time_in_zone(&config, spring, -60));
+        assert!(!valid_time_in_zone(&config, spring, 0));
+
+        let now = at("2024-03-10T06:22:30Z");
+        assert_eq!(verify_time(&config, now.timestamp_millis(), 240, None, now), Ok(()));
+        assert_eq!(verify_time(&config, now.timestamp_millis(), 300, None, now), Err(RejectReason::WrongTime));
+    }
+
+    #[test]
+    fn windows_across_midnight() {
+        let config = Config { hour: 0, minute: 0, ..Config::default() };
+
+        // The leeway before midnight is on the day before, so the distance to
+        // the middle of the minute wraps around
+        assert!(valid_time_in_zone(&config, at("2023-12-31T23:59:59.500Z"), 0));
+        assert!(!valid_time_in_zone(&config, at("2023-12-31T23:59:58.500Z"), 0));
+        assert!(valid_time_in_zone(&config, at("2023-12-31T22:59:59.500Z"), -60));
+        assert!(valid_time_in_zone(&config, at("2024-01-01T00:01:00.500Z"), 0));
+        assert!(!valid_time_in_zone(&config, at("2024-01-01T00:01:01.500Z"), 0));
+
+        // The window that's already passed today is 11 hours and 22 minutes
+        // away, in the other half of the day
+        let config = Config { allowed_offsets: Some(vec![0]), ..Config::default() };
+        assert_eq!(next_window(&config, at("2024-01-01T02:00:00Z")), Some((0, TimeDelta::minutes(22))));
+        assert_eq!(next_window(&config, at("2024-01-01T03:00:00Z")), Some((0, TimeDelta::minutes(11 * 60 + 22))));
+        assert_eq!(next_window(&config, at("2024-01-01T23:00:00Z")), Some((0, TimeDelta::minutes(3 * 60 + 22))));
+        assert_eq!(next_window(&config, at("2024-01-01T02:22:00Z")), None);
+    }
+
+    #[test]
+    fn seconds_or_milliseconds() {
+        let below = TimeUnit::SECONDS_BELOW;
+
+        assert_eq!(TimeUnit::to_millis(None, 1_704_075_750), 1_704_075_750_000);
+        assert_eq!(TimeUnit::to_millis(None, 1_704_075_750_000), 1_704_075_750_000);
+        assert_eq!(TimeUnit::to_millis(None, below - 1), (below - 1) * 1000);
+        assert_eq!(TimeUnit::to_millis(None, below), below);
+        assert_eq!(TimeUnit::to_millis(None, -(below - 1)), -(below - 1) * 1000);
+        assert_eq!(TimeUnit::to_millis(None, i64::MAX / 2), i64::MAX / 2);
+
+        // A unit wins over the guess
+        assert_eq!(TimeUnit::to_millis(Some(TimeUnit::Milliseconds), 1_704_075_750), 1_704_075_750);
+        assert_eq!(TimeUnit::to_millis(Some(TimeUnit::Seconds), below), below * 1000);
+        assert_eq!(TimeUnit::to_millis(Some(TimeUnit::Seconds), i64::MAX), i64::MAX);
+
+        let config = Config::default();
+        let now = at("2024-01-01T02:22:30Z");
+        for query in ["1704075750&0", "1704075750&0&unit=s", "1704075750000&0", "1704075750000&0&unit=ms"] {
+            let (time, offset) = try_parse_query(query).unwrap();
+            assert_eq!(verify_time(&config, time, offset, None, now), Ok(()), "{query}");
+        }
+
+        assert_eq!(try_parse_query("1704075750&0&unit=ns"), Err(QueryError::InvalidUnit("ns".into())));
+    }
+
+    #[test]
+    fn queries_with_other_parameters() {
+        let time = 1_704_075_750_000;
+
+        assert_eq!(try_parse_query("1704075750000&0"), Ok((time, 0)));
+        assert_eq!(try_parse_query("1704075750000&0&seed=5"), Ok((time, 0)));
+        assert_eq!(try_parse_query("1704075750000&0&seed=5&format=jpeg&"), Ok((time, 0)));
+        assert_eq!(try_parse_query("seed=5&1704075750000&&60"), Ok((time, 60)));
+        assert_eq!(try_parse_query("&1704075750000&-330&nonce=abc&rtt=100"), Ok((time, -330)));
+        assert_eq!(try_parse_query("1704075750000&60&7"), Ok((time, 60)));
+
+        // Named parameters work anywhere, and win over positional ones
+        assert_eq!(try_parse_query("time=1704075750000&offset=60"), Ok((time, 60)));
+        assert_eq!(try_parse_query("offset=60&seed=5&time=1704075750000"), Ok((time, 60)));
+        assert_eq!(try_parse_query("1&2&time=1704075750000&offset=60"), Ok((time, 60)));
+        assert_eq!(try_parse_query("1704075750000&offset=60"), Ok((time, 60)));
+
+        assert_eq!(try_parse_query("1704075750000"), Err(QueryError::MissingOffset));
+        assert_eq!(try_parse_query("1704075750000&seed=5"), Err(QueryError::MissingOffset));
+        assert_eq!(try_parse_query("seed=5"), Err(QueryError::InvalidTime("".into())));
+        assert_eq!(try_parse_query("now&60"), Err(QueryError::InvalidTime("now".into())));
+        assert_eq!(try_parse_query("1704075750000&west"), Err(QueryError::InvalidOffset("west".into())));
+        assert_eq!(parse_query("1704075750000&0&seed=5"), Some((time, 0)));
+    }
+
+    #[test]
+    fn offsets_in_seconds() {
+        let time = 1_704_075_750_000;
+
+        // Seconds are east of UTC, so UTC+05:30 is 19800 seconds but -330
+        // minutes
+        assert_eq!(OffsetUnit::to_minutes(Some(OffsetUnit::Seconds), 19800), Some(-330));
+        assert_eq!(OffsetUnit::to_minutes(Some(OffsetUnit::Seconds), -21600), Some(360));
+        assert_eq!(OffsetUnit::to_minutes(Some(OffsetUnit::Seconds), 19801), None);
+        assert_eq!(OffsetUnit::to_minutes(None, 19800), Some(19800));
+        assert_eq!(format_offset(-330), "UTC+05:30");
+
+        assert_eq!(try_parse_query("1704075750000&19800&offsetunit=s"), Ok((time, -330)));
+        assert_eq!(try_parse_query("offsetunit=s&1704075750000&-21600"), Ok((time, 360)));
+        assert_eq!(try_parse_query("1704075750000&-330&offsetunit=m"), Ok((time, -330)));
+        assert_eq!(try_parse_query("1704075750000&19801&offsetunit=s"), Err(QueryError::InvalidOffset("19801".into())));
+        assert_eq!(try_parse_query("1704075750000&19800&offsetunit=h"), Err(QueryError::InvalidOffsetUnit("h".into())));
+
+        // 2:22 in India is 20:52 UTC
+        let now = at("2023-12-31T20:52:30Z");
+        let (time, offset) = try_parse_query(&format!("{}&19800&offsetunit=s", now.timestamp())).unwrap();
+        assert_eq!(verify_time(&Config::default(), time, offset, None, now), Ok(()));
+    }
+
+    #[test]
+    fn furthest_offsets() {
+        assert_eq!(parse_offset("-12:00"), Some(WESTMOST_OFFSET));
+        assert_eq!(parse_offset("+14:00"), Some(EASTMOST_OFFSET));
+        assert_eq!(parse_offset(" +05:30 "), Some(-330));
+        assert_eq!(parse_offset("-12:01"), None);
+        assert_eq!(parse_offset("+14:01"), None);
+        assert_eq!(parse_offset("+05:60"), None);
+        assert_eq!(parse_offset("05:30"), None);
+
+        // Both ends are in the list the build script checked
+        assert!(valid_time_offsets().contains(&WESTMOST_OFFSET));
+        assert!(valid_time_offsets().contains(&EASTMOST_OFFSET));
+        assert!(valid_time_offsets().iter().all(|offset| (EASTMOST_OFFSET..=WESTMOST_OFFSET).contains(offset)));
+
+        // 2:22 at UTC+14:00 is 12:22 UTC the day before, and at UTC-12:00 it's
+        // 14:22 UTC the same day
+        let config = Config::default();
+        let east = at("2023-12-31T12:22:30Z");
+        assert!(valid_time_in_zone(&config, east, EASTMOST_OFFSET));
+        assert_eq!(verify_time(&config, east.timestamp_millis(), EASTMOST_OFFSET, None, east), Ok(()));
+        let west = at("2024-01-01T14:22:30Z");
+        assert!(valid_time_in_zone(&config, west, WESTMOST_OFFSET));
+        assert_eq!(verify_time(&config, west.timestamp_millis(), WESTMOST_OFFSET, None, west), Ok(()));
+    }
+}
@@ -0,0 +1,35 @@
+// The build script includes this file to check the time zone list, so it
+// can't use anything else from the crate.
+
+/// Parses an offset written like `+05:30` or `-06:00` into the way clients
+/// send offsets (e.g. 360 for `-06:00`). Offsets past the real extremes
+/// ([WESTMOST_OFFSET] and [EASTMOST_OFFSET]) aren't offsets at all.
+pub fn parse_offset(offset: &str) -> Option<i64> {
+    let offset = offset.trim();
+    let (sign, offset) = offset.split_at_checked(1)?;
+    let (hour, minute) = offset.split_once(':')?;
+
+    let sign = match sign {
+        "+" => 1,
+        "-" => -1,
+        _ => return None,
+    };
+
+    let hour = hour.parse::<u8>().ok()? as i64;
+    let minute = minute.parse::<u8>().ok().filter(|&minute| minute < 60)? as i64;
+
+    // Multiply -1 because offsets are negated;
+    // e.g. offset for UTC-06:00 is 360.
+    let offset = -sign * (hour * 60 + minute);
+
+    (EASTMOST_OFFSET..=WESTMOST_OFFSET).contains(&offset).then_some(offset)
+}
+
+/// The offset of the furthest west time zones, UTC-12:00 (like Baker Island),
+/// in the way clients send offsets.
+pub const WESTMOST_OFFSET: i64 = 12 * 60;
+
+/// The offset of the furthest east time zones, UTC+14:00 (like Kiribati's Line
+/// Islands), in the way clients send offsets. This is further from UTC than
+/// the westmost zones, so offsets aren't limited to ±12 hours.
+pub const EASTMOST_OFFSET: i64 = -14 * 60;
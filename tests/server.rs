@@ -0,0 +1,69 @@
+use makea_cat::config::Config;
+use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpStream};
+
+/// Sends a `GET` request to the server, returning the status, headers, and
+/// body of the response.
+async fn get(address: std::net::SocketAddr, path: &str) -> (u16, String, Vec<u8>) {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    stream.write_all(format!("GET {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").as_bytes()).await.unwrap();
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+
+    let end = response.windows(4).position(|window| window == b"\r\n\r\n").expect("the response has headers");
+    let head = String::from_utf8(response[..end].to_vec()).unwrap();
+    let status = head.split(' ').nth(1).and_then(|status| status.parse().ok()).unwrap();
+
+    (status, head.to_lowercase(), response[end + 4..].to_vec())
+}
+
+/// The default config, so the tests don't depend on the environment.
+fn config() -> &'static Config {
+    Box::leak(Box::default())
+}
+
+#[tokio::test]
+async fn fetches_a_cat() {
+    let address = makea_cat::testing::spawn(config()).await.unwrap();
+
+    let (status, headers, body) = get(address, "/cat?1&0&seed=5").await;
+    assert_eq!(status, 200);
+    assert!(headers.contains("content-type: image/png"));
+    assert!(headers.contains("x-cat-seed: 5"));
+    assert!(body.starts_with(b"\x89PNG\r\n\x1a\n"));
+
+    // The same seed is the same cat
+    let (_, _, again) = get(address, "/cat?1&0&seed=5").await;
+    assert_eq!(body, again);
+
+    // ...but there still has to be a time and offset, or it's a placeholder
+    let (status, headers, body) = get(address, "/cat?seed=5").await;
+    assert_eq!(status, 200);
+    assert!(!headers.contains("x-cat-seed"));
+    assert!(body.starts_with(b"\x89PNG\r\n\x1a\n"));
+}
+
+#[tokio::test]
+async fn streams_need_a_time() {
+    let address = makea_cat::testing::spawn(config()).await.unwrap();
+
+    // Without a time and offset, there's just a placeholder instead of a stream
+    let (status, headers, body) = get(address, "/cat/stream").await;
+    assert_eq!(status, 200);
+    assert!(headers.contains("content-type: image/png"));
+    assert!(body.starts_with(b"\x89PNG\r\n\x1a\n"));
+}
+
+#[tokio::test]
+async fn sizes_share_the_pixel_limit() {
+    let address = makea_cat::testing::spawn(config()).await.unwrap();
+
+    let (status, headers, _) = get(address, "/cat?1&0&sizes=400,800").await;
+    assert_eq!(status, 200);
+    assert!(headers.contains("content-type: application/json"));
+
+    // Each of these fits on its own, but not both together
+    let (status, _, body) = get(address, "/cat?1&0&sizes=2400,2400").await;
+    assert_eq!(status, 400);
+    assert_eq!(body, b"the image would be too big");
+}